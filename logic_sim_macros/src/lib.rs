@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    braced, parenthesized,
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    Attribute, Ident, LitBool, Token, Visibility,
+};
+
+/// Declares a struct holding a scene built from nets and gates, with a field for the pins of each input and output.
+///
+/// ```
+/// use logic_sim::{circuit, Stability};
+///
+/// circuit! {
+///     pub struct HalfAdder {
+///         inputs: a, b;
+///         let sum = and(or(a, b), nand(a, b));
+///         let carry = and(a, b);
+///         outputs: sum, carry;
+///     }
+/// }
+///
+/// let mut adder = HalfAdder::new();
+/// adder.set_a(true);
+/// adder.set_b(true);
+/// assert_eq!(adder.scene.run_until_stable(10), Stability::Stable { ticks: 1 });
+/// assert!(!adder.get_sum());
+/// assert!(adder.get_carry());
+/// ```
+///
+/// The body is made of
+///
+/// - `inputs: a, b;`, which become `Vec<Pin>` fields of every input pin that reads them, and `set_a` methods
+/// - `let name = gate(...);` where a gate is `not(a)`, `or(a, b)`, `nor(a, b)`, `and(a, b)`, `nand(a, b)`,
+///   or `delay(a)` and `delay(a, true)` to start high. Arguments are names or other gates
+/// - `assert always_high(...);`, `assert never_high(...);` and `assert equal(...);` on any number of signals
+/// - `outputs: x, y;`, which become `Pin` fields and `get_x` methods
+///
+/// Like with `CircuitBuilder`, every signal apart from an input can only be read once.
+/// Names can only be read after they are declared unless a delay reads them, which is how loops are made.
+/// Unknown names, gates given the wrong number of inputs and signals read twice are compile errors
+#[proc_macro]
+pub fn circuit(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let circuit = parse_macro_input!(input as CircuitDef);
+    Expander::default()
+        .expand(circuit)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+struct CircuitDef {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    name: Ident,
+    inputs: Vec<Ident>,
+    statements: Vec<Statement>,
+    outputs: Vec<Ident>,
+}
+
+enum Statement {
+    Let(Ident, Expr),
+    Assert(Ident, Vec<Arg>),
+}
+
+enum Expr {
+    Net(Ident),
+    Gate(Ident, Vec<Arg>),
+}
+
+enum Arg {
+    Expr(Expr),
+    State(LitBool),
+}
+
+impl Parse for CircuitDef {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<Token![struct]>()?;
+        let name = input.parse()?;
+        let content;
+        braced!(content in input);
+
+        let mut circuit = CircuitDef {
+            attrs,
+            vis,
+            name,
+            inputs: vec![],
+            statements: vec![],
+            outputs: vec![],
+        };
+        while !content.is_empty() {
+            if content.peek(Token![let]) {
+                content.parse::<Token![let]>()?;
+                let name = content.parse()?;
+                content.parse::<Token![=]>()?;
+                let expr = content.parse()?;
+                circuit.statements.push(Statement::Let(name, expr));
+            } else {
+                let keyword = content.parse::<Ident>()?;
+                match keyword.to_string().as_str() {
+                    "inputs" | "outputs" => {
+                        content.parse::<Token![:]>()?;
+                        let mut names = vec![content.parse::<Ident>()?];
+                        while content.peek(Token![,]) {
+                            content.parse::<Token![,]>()?;
+                            names.push(content.parse()?);
+                        }
+                        if keyword == "inputs" {
+                            circuit.inputs.extend(names);
+                        } else {
+                            circuit.outputs.extend(names);
+                        }
+                    }
+                    "assert" => {
+                        let condition = content.parse()?;
+                        let args;
+                        parenthesized!(args in content);
+                        let args = Punctuated::<Arg, Token![,]>::parse_terminated(&args)?;
+                        circuit
+                            .statements
+                            .push(Statement::Assert(condition, args.into_iter().collect()));
+                    }
+                    _ => {
+                        return Err(syn::Error::new(
+                            keyword.span(),
+                            "expected `inputs:`, `let`, `assert` or `outputs:`",
+                        ))
+                    }
+                }
+            }
+            content.parse::<Token![;]>()?;
+        }
+        Ok(circuit)
+    }
+}
+
+impl Parse for Expr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse::<Ident>()?;
+        if !input.peek(syn::token::Paren) {
+            return Ok(Expr::Net(name));
+        }
+        let args;
+        parenthesized!(args in input);
+        let args = Punctuated::<Arg, Token![,]>::parse_terminated(&args)?;
+        Ok(Expr::Gate(name, args.into_iter().collect()))
+    }
+}
+
+impl Parse for Arg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(LitBool) {
+            Ok(Arg::State(input.parse()?))
+        } else {
+            Ok(Arg::Expr(input.parse()?))
+        }
+    }
+}
+
+impl Arg {
+    fn span(&self) -> Span {
+        match self {
+            Arg::Expr(Expr::Net(name) | Expr::Gate(name, _)) => name.span(),
+            Arg::State(state) => state.span,
+        }
+    }
+}
+
+const GATES: &str = "`not`, `or`, `nor`, `and`, `nand` or `delay`";
+
+enum Net {
+    Input,
+    Gate { defined: bool, read: bool },
+}
+
+/// Turns the declarations into `CircuitBuilder` calls, checking names and pin counts along the way
+#[derive(Default)]
+struct Expander {
+    nets: HashMap<String, Net>,
+    body: Vec<TokenStream>,
+    // Delays whose input is connected once every name has been declared
+    deferred: Vec<(Ident, Expr)>,
+    wires: usize,
+}
+
+// The builder and temporaries are hygienic so that signals with the same names cannot shadow them
+fn hygienic(name: &str) -> Ident {
+    Ident::new(name, Span::mixed_site())
+}
+
+impl Expander {
+    fn expand(mut self, circuit: CircuitDef) -> syn::Result<TokenStream> {
+        let builder = hygienic("builder");
+        for name in &circuit.inputs {
+            self.declare(name, Net::Input)?;
+        }
+        let nets = circuit
+            .statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Let(name, _) => Some(name.clone()),
+                Statement::Assert(..) => None,
+            })
+            .collect::<Vec<_>>();
+        for name in &nets {
+            self.declare(
+                name,
+                Net::Gate {
+                    defined: false,
+                    read: false,
+                },
+            )?;
+        }
+
+        // Requesting every input first keeps them in the order they were declared
+        for name in &circuit.inputs {
+            let name = name.to_string();
+            self.body.push(quote!(let _ = #builder.input(#name);));
+        }
+        for statement in circuit.statements {
+            match statement {
+                Statement::Let(name, expr) => {
+                    let wire = self.wire(expr)?;
+                    self.body.push(quote!(let #name = #wire;));
+                    self.nets.insert(
+                        name.to_string(),
+                        Net::Gate {
+                            defined: true,
+                            read: false,
+                        },
+                    );
+                }
+                Statement::Assert(condition, args) => {
+                    let condition = match condition.to_string().as_str() {
+                        "always_high" => quote!(AlwaysHigh),
+                        "never_high" => quote!(NeverHigh),
+                        "equal" => quote!(Equal),
+                        _ => {
+                            return Err(syn::Error::new(
+                                condition.span(),
+                                format!(
+                                    "unknown condition `{condition}`, expected `always_high`, `never_high` or `equal`"
+                                ),
+                            ))
+                        }
+                    };
+                    if args.is_empty() {
+                        return Err(syn::Error::new(
+                            Span::call_site(),
+                            "asserts need at least one input",
+                        ));
+                    }
+                    let wires = args
+                        .into_iter()
+                        .map(|arg| self.arg(arg))
+                        .collect::<syn::Result<Vec<_>>>()?;
+                    self.body.push(quote!(
+                        #builder.assert(::logic_sim::AssertCondition::#condition, [#(#wires),*]);
+                    ));
+                }
+            }
+        }
+        while !self.deferred.is_empty() {
+            for (feedback, expr) in std::mem::take(&mut self.deferred) {
+                let wire = self.wire(expr)?;
+                self.body.push(quote!(#builder.close(#feedback, #wire);));
+            }
+        }
+
+        for name in &circuit.outputs {
+            if circuit.inputs.contains(name) {
+                return Err(syn::Error::new(
+                    name.span(),
+                    format!("`{name}` is already an input, so it cannot also be an output"),
+                ));
+            }
+            let wire = self.read(name)?;
+            let label = name.to_string();
+            self.body.push(quote!(#builder.output(#label, #wire);));
+        }
+        // Nothing reads these, so their outputs stay unconnected
+        for name in &nets {
+            if let Some(Net::Gate { read: false, .. }) = self.nets.get(&name.to_string()) {
+                self.body.push(quote!(let _ = #name;));
+            }
+        }
+
+        let CircuitDef {
+            attrs,
+            vis,
+            name,
+            inputs,
+            outputs,
+            ..
+        } = circuit;
+        let body = self.body;
+        let setters = inputs.iter().map(|input| format_ident!("set_{input}"));
+        let getters = outputs.iter().map(|output| format_ident!("get_{output}"));
+        Ok(quote! {
+            #(#attrs)*
+            #vis struct #name {
+                pub scene: ::logic_sim::Scene,
+                #(pub #inputs: ::std::vec::Vec<::logic_sim::Pin>,)*
+                #(pub #outputs: ::logic_sim::Pin,)*
+            }
+
+            impl #name {
+                pub fn new() -> Self {
+                    let mut #builder = ::logic_sim::CircuitBuilder::new();
+                    #(#body)*
+                    let circuit = #builder.build();
+                    let mut inputs = circuit.inputs.into_iter();
+                    let mut outputs = circuit.outputs.into_iter();
+                    Self {
+                        #(#inputs: inputs.next().unwrap().1,)*
+                        #(#outputs: outputs.next().unwrap().1,)*
+                        scene: circuit.scene,
+                    }
+                }
+
+                #(
+                    pub fn #setters(&mut self, state: bool) {
+                        for &pin in &self.#inputs {
+                            if let ::logic_sim::Pin::Input(id, index) = pin {
+                                self.scene.get_component_mut(id).get_inputs_mut()[index].state = state;
+                            }
+                        }
+                    }
+                )*
+
+                #(
+                    pub fn #getters(&self) -> bool {
+                        self.scene.get_pin_state(self.#outputs)
+                    }
+                )*
+            }
+
+            impl ::std::default::Default for #name {
+                fn default() -> Self {
+                    Self::new()
+                }
+            }
+        })
+    }
+
+    fn declare(&mut self, name: &Ident, net: Net) -> syn::Result<()> {
+        if name == "scene" {
+            return Err(syn::Error::new(
+                name.span(),
+                "`scene` is the field the scene is kept in, so it cannot name a signal",
+            ));
+        }
+        if self.nets.insert(name.to_string(), net).is_some() {
+            return Err(syn::Error::new(
+                name.span(),
+                format!("`{name}` is declared more than once"),
+            ));
+        }
+        Ok(())
+    }
+
+    // Emits whatever `expr` needs and returns an expression for its wire
+    fn wire(&mut self, expr: Expr) -> syn::Result<TokenStream> {
+        let (gate, args) = match expr {
+            Expr::Net(name) => return self.read(&name),
+            Expr::Gate(gate, args) => (gate, args),
+        };
+        let inputs = match gate.to_string().as_str() {
+            "not" => 1,
+            "or" | "nor" | "and" | "nand" => 2,
+            "delay" => return self.delay(gate, args),
+            _ => {
+                let message = format!("unknown gate `{gate}`, expected {GATES}");
+                return Err(syn::Error::new(gate.span(), message));
+            }
+        };
+        if args.len() != inputs {
+            return Err(syn::Error::new(
+                gate.span(),
+                format!(
+                    "`{gate}` takes {inputs} input{} but was given {}",
+                    if inputs == 1 { "" } else { "s" },
+                    args.len()
+                ),
+            ));
+        }
+        let wires = args
+            .into_iter()
+            .map(|arg| self.arg(arg))
+            .collect::<syn::Result<Vec<_>>>()?;
+        let builder = hygienic("builder");
+        let wire = self.temporary("wire");
+        self.body
+            .push(quote!(let #wire = #builder.#gate(#(#wires),*);));
+        Ok(quote!(#wire))
+    }
+
+    // A delay only needs its input connected once everything is declared, which lets it read names declared later
+    fn delay(&mut self, gate: Ident, args: Vec<Arg>) -> syn::Result<TokenStream> {
+        let mut args = args.into_iter();
+        let (input, state) = match (args.next(), args.next(), args.next()) {
+            (Some(Arg::Expr(input)), None, None) => (input, false),
+            (Some(Arg::Expr(input)), Some(Arg::State(state)), None) => (input, state.value),
+            (Some(Arg::Expr(_)), Some(arg), None) => {
+                return Err(syn::Error::new(
+                    arg.span(),
+                    "the initial state of a delay should be `true` or `false`",
+                ))
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    gate.span(),
+                    "`delay` takes 1 input and an optional initial state",
+                ))
+            }
+        };
+        let builder = hygienic("builder");
+        let wire = self.temporary("wire");
+        let feedback = self.temporary("feedback");
+        self.body
+            .push(quote!(let (#wire, #feedback) = #builder.feedback(#state);));
+        self.deferred.push((feedback, input));
+        Ok(quote!(#wire))
+    }
+
+    fn arg(&mut self, arg: Arg) -> syn::Result<TokenStream> {
+        match arg {
+            Arg::Expr(expr) => self.wire(expr),
+            Arg::State(state) => Err(syn::Error::new(
+                state.span,
+                "only delays take an initial state",
+            )),
+        }
+    }
+
+    fn read(&mut self, name: &Ident) -> syn::Result<TokenStream> {
+        match self.nets.get_mut(&name.to_string()) {
+            None => Err(syn::Error::new(
+                name.span(),
+                format!("unknown name `{name}`"),
+            )),
+            Some(Net::Input) => {
+                let label = name.to_string();
+                let builder = hygienic("builder");
+                let wire = self.temporary("wire");
+                self.body
+                    .push(quote!(let #wire = #builder.input(#label);));
+                Ok(quote!(#wire))
+            }
+            Some(Net::Gate { defined: false, .. }) => Err(syn::Error::new(
+                name.span(),
+                format!("`{name}` is read before it is declared, which only a delay can do"),
+            )),
+            Some(Net::Gate { read: true, .. }) => Err(syn::Error::new(
+                name.span(),
+                format!("`{name}` is already read by something else, and an output can only drive one input"),
+            )),
+            Some(Net::Gate { read, .. }) => {
+                *read = true;
+                Ok(quote!(#name))
+            }
+        }
+    }
+
+    fn temporary(&mut self, kind: &str) -> Ident {
+        self.wires += 1;
+        hygienic(&format!("{kind}_{}", self.wires))
+    }
+}
@@ -0,0 +1,12 @@
+use logic_sim::circuit;
+
+circuit! {
+    struct Broken {
+        inputs: a;
+        let x = not(a);
+        let y = and(x, x);
+        outputs: y;
+    }
+}
+
+fn main() {}
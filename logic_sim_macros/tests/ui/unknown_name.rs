@@ -0,0 +1,11 @@
+use logic_sim::circuit;
+
+circuit! {
+    struct Broken {
+        inputs: a;
+        let x = not(b);
+        outputs: x;
+    }
+}
+
+fn main() {}
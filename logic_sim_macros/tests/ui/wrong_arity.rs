@@ -0,0 +1,11 @@
+use logic_sim::circuit;
+
+circuit! {
+    struct Broken {
+        inputs: a, b;
+        let x = and(a);
+        outputs: x;
+    }
+}
+
+fn main() {}
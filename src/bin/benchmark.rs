@@ -0,0 +1,196 @@
+use std::{
+    num::NonZeroUsize,
+    time::{Duration, Instant},
+};
+
+use logic_sim::*;
+
+const GATE_COUNT: usize = 4000;
+const UPDATE_COUNT: usize = 200;
+const EXHAUSTIVE_INPUT_COUNT: usize = 16;
+const PARALLEL_GATE_COUNT: usize = 400_000;
+
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn random_circuit(
+    random: &mut Random,
+    gate_count: usize,
+) -> (Scene, Vec<ComponentID>, Vec<(ComponentID, usize)>) {
+    let mut scene = Scene::new();
+    let ids = (0..gate_count)
+        .map(|_| {
+            scene.add_component(Component {
+                typ: if random.below(3) == 0 {
+                    ComponentType::Not {
+                        input: Input { state: false },
+                        output: None,
+                    }
+                } else {
+                    ComponentType::Or {
+                        inputs: [Input { state: false }, Input { state: false }],
+                        output: None,
+                    }
+                },
+                position: Default::default(),
+                labels: Default::default(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Walking backwards means every gate can only drive gates after it, so there are no loops
+    let mut free_inputs = vec![];
+    for &id in ids.iter().rev() {
+        if !free_inputs.is_empty() && random.below(10) != 0 {
+            let (component, index) = free_inputs.swap_remove(random.below(free_inputs.len()));
+            scene.get_component_mut(id).get_outputs_mut()[0] = Some(Output { component, index });
+        }
+        for index in 0..scene.get_component(id).get_inputs().len() {
+            free_inputs.push((id, index));
+        }
+    }
+    (scene, ids, free_inputs)
+}
+
+fn main() {
+    let mut random = Random(0x2545F4914F6CDD1D);
+    let (mut scene, ids, free_inputs) = random_circuit(&mut random, GATE_COUNT);
+
+    let compile_start = Instant::now();
+    let mut compiled = CompiledScene::new(&scene);
+    let compile_time = compile_start.elapsed();
+    scene.update();
+
+    let mut scene_time = Duration::ZERO;
+    let mut compiled_time = Duration::ZERO;
+    for _ in 0..UPDATE_COUNT {
+        for _ in 0..8 {
+            let (id, index) = free_inputs[random.below(free_inputs.len())];
+            let state = random.below(2) == 0;
+            scene.get_component_mut(id).get_inputs_mut()[index].state = state;
+            compiled.set_input_state(id, index, state);
+        }
+
+        let start = Instant::now();
+        scene.update();
+        scene_time += start.elapsed();
+
+        let start = Instant::now();
+        compiled.update();
+        compiled_time += start.elapsed();
+
+        for id in &scene {
+            for (index, input) in scene.get_component(id).get_inputs().iter().enumerate() {
+                assert_eq!(
+                    input.state,
+                    compiled.get_input_state(id, index),
+                    "Component {id}, Input {index} differs between the scene and the compiled scene"
+                );
+            }
+        }
+    }
+
+    println!(
+        "{GATE_COUNT} gates, {} levels, {UPDATE_COUNT} updates",
+        compiled.level_count()
+    );
+    println!("Compile:        {compile_time:?}");
+    println!("Scene::update:  {scene_time:?}");
+    println!("CompiledScene:  {compiled_time:?}");
+    println!(
+        "Speedup:        {:.1}x",
+        scene_time.as_secs_f64() / compiled_time.as_secs_f64()
+    );
+
+    let inputs = free_inputs[..EXHAUSTIVE_INPUT_COUNT]
+        .iter()
+        .map(|&(id, index)| Pin::Input(id, index))
+        .collect::<Vec<_>>();
+    let outputs = ids
+        .iter()
+        .filter(|&&id| scene.get_component(id).get_outputs()[0].is_none())
+        .map(|&id| Pin::Output(id, 0))
+        .collect::<Vec<_>>();
+    let mut batch = BatchScene::new(&scene);
+    let mut results = vec![];
+    let start = Instant::now();
+    batch.for_each_combination(&inputs, &outputs, |_, states| {
+        results.extend_from_slice(states)
+    });
+    let batch_time = start.elapsed();
+
+    for _ in 0..64 {
+        let combination = random.below(1 << EXHAUSTIVE_INPUT_COUNT);
+        for (i, &(id, index)) in free_inputs[..EXHAUSTIVE_INPUT_COUNT].iter().enumerate() {
+            compiled.set_input_state(id, index, combination & (1 << i) != 0);
+        }
+        compiled.update();
+        for (i, &output) in outputs.iter().enumerate() {
+            let state =
+                results[combination / 64 * outputs.len() + i] & (1 << (combination % 64)) != 0;
+            assert_eq!(
+                state,
+                compiled.get_pin_state(output),
+                "{output} differs between the batch and the compiled scene"
+            );
+        }
+    }
+
+    println!(
+        "All {} combinations of {EXHAUSTIVE_INPUT_COUNT} inputs, {} outputs: {batch_time:?}",
+        1 << EXHAUSTIVE_INPUT_COUNT,
+        outputs.len()
+    );
+
+    let threads = std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN);
+    let (scene, _, free_inputs) = random_circuit(&mut random, PARALLEL_GATE_COUNT);
+    let mut serial = CompiledScene::new(&scene);
+    let mut parallel = serial.clone();
+    let mut serial_time = Duration::ZERO;
+    let mut parallel_time = Duration::ZERO;
+    for _ in 0..UPDATE_COUNT {
+        for _ in 0..64 {
+            let (id, index) = free_inputs[random.below(free_inputs.len())];
+            let state = random.below(2) == 0;
+            serial.set_input_state(id, index, state);
+            parallel.set_input_state(id, index, state);
+        }
+
+        let start = Instant::now();
+        serial.update();
+        serial_time += start.elapsed();
+
+        let start = Instant::now();
+        parallel.update_parallel(threads);
+        parallel_time += start.elapsed();
+    }
+    for id in &scene {
+        for index in 0..scene.get_component(id).get_inputs().len() {
+            assert_eq!(
+                serial.get_input_state(id, index),
+                parallel.get_input_state(id, index),
+                "Component {id}, Input {index} differs between the serial and parallel updates"
+            );
+        }
+    }
+
+    println!();
+    println!(
+        "{PARALLEL_GATE_COUNT} gates, {} levels, {UPDATE_COUNT} updates, {threads} threads",
+        serial.level_count()
+    );
+    println!("Serial:         {serial_time:?}");
+    println!("Parallel:       {parallel_time:?}");
+}
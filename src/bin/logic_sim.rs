@@ -0,0 +1,353 @@
+use std::{
+    io::{self, BufRead, Write},
+    process::ExitCode,
+};
+
+use logic_sim::*;
+use raylib::math::Vector2;
+
+const USAGE: &str = "Usage: logic_sim [scene.json] [options]
+
+Options:
+  --stimulus <file>       Test vectors to apply, with lines like '3 set 0.1 1' and '5 expect 2.out0 0'
+  --set <pin>=<0|1>       Sets an input that nothing is connected to before the first tick
+  --set <port>=<value>    Sets an input port before the first tick, to a value like 5, 0b101 or 0x5
+  --ticks <n>             Runs for n ticks after the stimulus
+  --until-stable [max]    Runs after the stimulus until nothing changes, for at most max ticks (1000 by default).
+                          This is what runs when --ticks is not given
+  --format <text|json|vcd|ports>
+                          Prints the final scene, the final scene as JSON, a waveform of the run,
+                          or the final value of every port
+  --watch <pin>           Pins to include in the waveform, every pin by default
+  --output <file>         Writes the results to a file instead of printing them
+  --lint                  Prints anything in the scene that looks like a mistake instead of running it,
+                          and exits with 1 if there is anything
+  --repl                  Reads commands like 'step 10' from standard input instead of running, try 'help'.
+                          It cannot be combined with the other options
+  --script <file>         Runs a Rhai script instead, which can build the scene when none is given.
+                          The scene is only printed afterwards if --format or --output is given
+
+Exits with 1 if an assert fails, an expectation does not hold or the scene oscillates or does not settle
+when run until it is stable, and 2 if the arguments are wrong";
+
+enum Run {
+    Ticks(usize),
+    UntilStable(usize),
+}
+
+enum Format {
+    Text,
+    Json,
+    Vcd,
+    Ports,
+}
+
+struct Options {
+    scene_path: Option<String>,
+    stimulus_path: Option<String>,
+    sets: Vec<(String, u64)>,
+    run: Run,
+    format: Option<Format>,
+    watched: Vec<String>,
+    output_path: Option<String>,
+    repl: bool,
+    lint: bool,
+    script_path: Option<String>,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut args = args.iter();
+    let mut options = Options {
+        scene_path: None,
+        stimulus_path: None,
+        sets: vec![],
+        run: Run::UntilStable(1000),
+        format: None,
+        watched: vec![],
+        output_path: None,
+        repl: false,
+        lint: false,
+        script_path: None,
+    };
+    // Anything besides the scene that would be ignored by the REPL
+    let mut flags = vec![];
+    while let Some(arg) = args.next() {
+        if arg.starts_with("--") && arg != "--repl" {
+            flags.push(arg.as_str());
+        }
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{arg} needs a value"))
+        };
+        match arg.as_str() {
+            "--stimulus" => options.stimulus_path = Some(value()?),
+            "--set" => {
+                let set = value()?;
+                let (name, value) = set
+                    .split_once('=')
+                    .and_then(|(name, value)| Some((name.to_string(), parse_port_value(value)?)))
+                    .ok_or_else(|| {
+                        format!("'{set}' should look like <pin>=<0|1> or <port>=<value>")
+                    })?;
+                options.sets.push((name, value));
+            }
+            "--ticks" => {
+                let ticks = value()?;
+                options.run = Run::Ticks(
+                    ticks
+                        .parse()
+                        .map_err(|_| format!("'{ticks}' is not a number of ticks"))?,
+                );
+            }
+            "--until-stable" => {
+                let max_ticks = args.as_slice().first().and_then(|max| max.parse().ok());
+                if max_ticks.is_some() {
+                    args.next();
+                }
+                options.run = Run::UntilStable(max_ticks.unwrap_or(1000));
+            }
+            "--format" => {
+                options.format = Some(match value()?.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    "vcd" => Format::Vcd,
+                    "ports" => Format::Ports,
+                    format => return Err(format!("Unknown format '{format}'")),
+                })
+            }
+            "--watch" => options.watched.push(value()?),
+            "--output" => options.output_path = Some(value()?),
+            "--repl" => options.repl = true,
+            "--lint" => options.lint = true,
+            "--script" => options.script_path = Some(value()?),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {arg}")),
+            _ if options.scene_path.is_none() => options.scene_path = Some(arg.clone()),
+            _ => return Err(format!("Unexpected argument {arg}")),
+        }
+    }
+    if options.repl && !flags.is_empty() {
+        return Err(format!("--repl cannot be used with {}", flags.join(" or ")));
+    }
+    if options.scene_path.is_none() && options.script_path.is_none() {
+        return Err("No scene was given".into());
+    }
+    if options.script_path.is_some() && matches!(options.format, Some(Format::Vcd)) {
+        return Err("Scripts run the scene themselves, so there is no waveform to write".into());
+    }
+    Ok(options)
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let options = match parse_options(&args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{error}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match simulate(&options) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+// Returns whether every assert and expectation held
+fn simulate(options: &Options) -> Result<bool, String> {
+    let read = |path: &str| {
+        std::fs::read_to_string(path).map_err(|error| format!("Could not read {path}: {error}"))
+    };
+    let mut scene = match &options.scene_path {
+        Some(path) => serde_json::from_str::<Scene>(&read(path)?)
+            .map_err(|error| format!("Could not load {path}: {error}"))?,
+        None => Scene::new(),
+    };
+    let stimulus = match &options.stimulus_path {
+        Some(path) => read(path)?
+            .parse::<TestVectors>()
+            .map_err(|error| format!("{path}: {error}"))?,
+        None => "".parse::<TestVectors>().unwrap(),
+    };
+
+    for (name, value) in &options.sets {
+        if scene.find_port(name).is_ok() {
+            scene
+                .set_port(name, *value)
+                .map_err(|error| error.to_string())?;
+            continue;
+        }
+        match scene.find_pin(name) {
+            Some(Pin::Input(..)) if *value > 1 => {
+                return Err(format!("{name} is a pin, so it can only be 0 or 1"))
+            }
+            Some(Pin::Input(id, index)) if scene.get_driver(id, index).is_none() => {
+                scene.get_component_mut(id).get_inputs_mut()[index].state = *value != 0;
+            }
+            Some(_) => {
+                return Err(format!(
+                    "{name} is not an input that nothing is connected to"
+                ))
+            }
+            None => return Err(format!("There is no pin or port {name}")),
+        }
+    }
+
+    if options.lint {
+        let diagnostics = scene.lint();
+        for diagnostic in &diagnostics {
+            println!("{diagnostic}");
+        }
+        return Ok(diagnostics.is_empty());
+    }
+
+    if options.repl {
+        repl(scene);
+        return Ok(true);
+    }
+
+    if let Some(path) = &options.script_path {
+        let report = ScriptEngine::new()
+            .run(&mut scene, &read(path)?, Vector2::zero())
+            .map_err(|error| format!("{path}: {error}"))?;
+        for line in &report.output {
+            println!("{line}");
+        }
+        if options.format.is_some() || options.output_path.is_some() {
+            write_results(options, &scene, &Tracer::new())?;
+        }
+        for mismatch in &report.mismatches {
+            eprintln!("{mismatch}");
+        }
+        for violation in &report.violations {
+            eprintln!("{violation}");
+        }
+        return Ok(report.mismatches.is_empty() && report.violations.is_empty());
+    }
+
+    let mut tracer = Tracer::new();
+    if matches!(options.format, Some(Format::Vcd)) {
+        if options.watched.is_empty() {
+            for id in &scene {
+                let component = scene.get_component(id);
+                for index in 0..component.get_inputs().len() {
+                    tracer.watch(&scene, Pin::Input(id, index));
+                }
+                for index in 0..component.get_outputs().len() {
+                    tracer.watch(&scene, Pin::Output(id, index));
+                }
+            }
+        }
+        for name in &options.watched {
+            let pin = scene
+                .find_pin(name)
+                .ok_or_else(|| format!("There is no pin {name}"))?;
+            tracer.watch(&scene, pin);
+        }
+    }
+
+    tracer.sample(&scene);
+
+    let apply = |scene: &mut Scene, tick: usize| {
+        stimulus.apply(scene, tick).map_err(|error| {
+            format!(
+                "{}: {error}",
+                options.stimulus_path.as_deref().unwrap_or_default()
+            )
+        })
+    };
+    let mut violations = vec![];
+    let mut update = |scene: &mut Scene| {
+        let update = tracer.update(scene);
+        violations.extend(update.violations);
+        update.had_changes
+    };
+
+    let mut mismatches = apply(&mut scene, 0)?;
+    for tick in 1..=stimulus.last_tick() {
+        update(&mut scene);
+        mismatches.extend(apply(&mut scene, tick)?);
+    }
+    let settled = match options.run {
+        Run::Ticks(ticks) => {
+            for _ in 0..ticks {
+                update(&mut scene);
+            }
+            true
+        }
+        Run::UntilStable(max_ticks) => {
+            let stability = scene.run_until_stable_with(max_ticks, |scene, update| {
+                tracer.sample(scene);
+                violations.extend(update.violations);
+            });
+            eprintln!("{stability}");
+            matches!(stability, Stability::Stable { .. })
+        }
+    };
+
+    write_results(options, &scene, &tracer)?;
+
+    for mismatch in &mismatches {
+        eprintln!("{mismatch}");
+    }
+    for violation in &violations {
+        eprintln!("{violation}");
+    }
+    Ok(settled && mismatches.is_empty() && violations.is_empty())
+}
+
+fn write_results(options: &Options, scene: &Scene, tracer: &Tracer) -> Result<(), String> {
+    let mut results = vec![];
+    match options.format.as_ref().unwrap_or(&Format::Text) {
+        Format::Text => write!(results, "{scene}").unwrap(),
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut results, scene).unwrap();
+            writeln!(results).unwrap();
+        }
+        Format::Vcd => tracer.write_vcd(&mut results).unwrap(),
+        Format::Ports => {
+            for port in scene.ports() {
+                writeln!(
+                    results,
+                    "{} = {}",
+                    port.name,
+                    scene.get_port(&port.name).unwrap()
+                )
+                .unwrap();
+            }
+        }
+    }
+    match &options.output_path {
+        Some(path) => std::fs::write(path, results)
+            .map_err(|error| format!("Could not write {path}: {error}")),
+        None => {
+            io::stdout().write_all(&results).unwrap();
+            Ok(())
+        }
+    }
+}
+
+fn repl(scene: Scene) {
+    let mut repl = Repl::new(scene);
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        let Some(Ok(line)) = lines.next() else {
+            println!();
+            break;
+        };
+        if matches!(line.trim(), "quit" | "exit") {
+            break;
+        }
+        match repl.execute(&line) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{}", output.trim_end()),
+            Err(error) => println!("{error}"),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use lerp::Lerp;
 use raylib::prelude::*;
 
@@ -15,6 +17,11 @@ enum Selected {
     },
 }
 
+enum LabelTarget {
+    Component(ComponentID),
+    Pin(Pin),
+}
+
 fn main() {
     const WIDTH: usize = 640;
     const HEIGHT: usize = 480;
@@ -35,45 +42,401 @@ fn main() {
         zoom: 1.0,
     };
 
-    let mut scene = Scene::new();
-    scene.add_component(Component {
-        typ: ComponentType::Not {
-            input: Input { state: false },
-            output: None,
-        },
-        position: Vector2 { x: 100.0, y: 100.0 },
-    });
-    scene.add_component(Component {
-        typ: ComponentType::Or {
-            inputs: [Input { state: false }, Input { state: false }],
-            output: None,
-        },
-        position: Vector2 { x: 100.0, y: -50.0 },
-    });
-    scene.add_component(Component {
-        typ: ComponentType::Delay {
-            input: Input { state: false },
-            output: None,
-            state_last_frame: false,
-        },
-        position: Vector2 { x: -100.0, y: 0.0 },
-    });
+    let path = std::env::args().nth(1);
+    let mut scene = if let Some(path) = &path {
+        let text = std::fs::read_to_string(path)
+            .unwrap_or_else(|error| panic!("Could not read {path}: {error}"));
+        if path.ends_with(".json") {
+            serde_json::from_str(&text)
+                .unwrap_or_else(|error| panic!("Could not load {path}: {error}"))
+        } else {
+            match import_blif(&text) {
+                Ok(netlist) => netlist.scene,
+                Err(error) => panic!("Could not import {path}: {error}"),
+            }
+        }
+    } else {
+        let mut scene = Scene::new();
+        scene.add_component(Component {
+            typ: ComponentType::Not {
+                input: Input { state: false },
+                output: None,
+            },
+            position: Vector2 { x: 100.0, y: 100.0 },
+            labels: Default::default(),
+        });
+        scene.add_component(Component {
+            typ: ComponentType::Or {
+                inputs: [Input { state: false }, Input { state: false }],
+                output: None,
+            },
+            position: Vector2 { x: 100.0, y: -50.0 },
+            labels: Default::default(),
+        });
+        scene.add_component(Component {
+            typ: ComponentType::Delay {
+                input: Input { state: false },
+                output: None,
+                state_last_frame: false,
+            },
+            position: Vector2 { x: -100.0, y: 0.0 },
+            labels: Default::default(),
+        });
+        scene
+    };
 
     let mut selected = Selected::Nothing;
 
+    const HISTORY_LENGTH: usize = 1000;
+    let mut history = History::new(&scene, HISTORY_LENGTH);
+    let mut paused = false;
+
+    const WAVEFORM_ROW_HEIGHT: f32 = 24.0;
+    const WAVEFORM_NAME_WIDTH: f32 = 140.0;
+    let mut waveforms = Tracer::with_max_samples(HISTORY_LENGTH + 1);
+    waveforms.sample(&scene);
+    let mut waveform_tick_width = 8.0;
+
+    const TRUTH_TABLE_MAX_TICKS: usize = 1000;
+    let mut truth_table: Option<Result<TruthTable, TruthTableError>> = None;
+
+    let mut expression_prompt: Option<String> = None;
+    let mut synthesis_error: Option<String> = None;
+
+    let mut label_prompt: Option<(LabelTarget, String)> = None;
+
+    // Linting looks at the whole scene, so it is only done again after the scene is edited
+    let mut diagnostics: Option<HashMap<ComponentID, Vec<String>>> = None;
+
+    const CONSOLE_LINES: usize = 8;
+    let mut scripts = ScriptEngine::new();
+    let mut console_prompt: Option<String> = None;
+    let mut console_lines: Vec<(String, Color)> = vec![];
+
+    const VIOLATION_FLASH_TIME: f32 = 1.0;
+    let mut violation_flashes: HashMap<ComponentID, f32> = HashMap::new();
+    let mut last_violation: Option<Violation> = None;
+
+    let timeline = Rectangle {
+        x: 12.0,
+        y: HEIGHT as f32 - 32.0,
+        width: WIDTH as f32 - 24.0,
+        height: 20.0,
+    };
+
     const UPDATE_INTERVAL: f32 = 1.0 / 5.0;
     let mut update_time = 0.0;
     while !rl.window_should_close() {
         let ts = rl.get_frame_time();
 
-        update_time += ts;
-        while update_time >= UPDATE_INTERVAL {
-            scene.update();
-            update_time -= UPDATE_INTERVAL;
+        let mut violations = vec![];
+        if !paused {
+            update_time += ts;
+            while update_time >= UPDATE_INTERVAL {
+                violations.extend(history.update(&mut scene).violations);
+                record_waveforms(&mut waveforms, &history, &scene);
+                update_time -= UPDATE_INTERVAL;
+            }
         }
 
-        // Movement
+        // Synthesis
+        let typing =
+            expression_prompt.is_some() || console_prompt.is_some() || label_prompt.is_some();
         {
+            let mut synthesised = None;
+            if let Some(text) = &mut expression_prompt {
+                edit_text(&rl, text);
+                if rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+                    // Entering nothing closes the prompt
+                    synthesised = if text.trim().is_empty() {
+                        Some(Ok(vec![]))
+                    } else {
+                        match text.parse::<Expression>() {
+                            Ok(expression) => Some(Ok(vec![(text.clone(), expression)])),
+                            Err(error) => {
+                                synthesis_error = Some(error.to_string());
+                                None
+                            }
+                        }
+                    };
+                }
+            } else if !typing {
+                if rl.is_key_pressed(KeyboardKey::KEY_E) {
+                    expression_prompt = Some(String::new());
+                    synthesis_error = None;
+                }
+                if rl.is_key_pressed(KeyboardKey::KEY_V) {
+                    synthesised = Some(
+                        rl.get_clipboard_text()
+                            .map_err(|error| error.to_string())
+                            .and_then(|text| {
+                                TruthTable::parse(&text).map_err(|error| error.to_string())
+                            })
+                            .and_then(|truth_table| {
+                                truth_table
+                                    .to_expressions()
+                                    .map_err(|error| error.to_string())
+                            }),
+                    );
+                }
+            }
+            match synthesised {
+                Some(Ok(outputs)) => {
+                    expression_prompt = None;
+                    synthesis_error = None;
+                    if !outputs.is_empty() {
+                        Synthesis::insert(&mut scene, &outputs, camera.target);
+                        history.clear(&scene);
+                        diagnostics = None;
+                        record_waveforms(&mut waveforms, &history, &scene);
+                    }
+                }
+                Some(Err(error)) => synthesis_error = Some(error),
+                None => {}
+            }
+        }
+
+        // Console
+        {
+            if let Some(text) = &mut console_prompt {
+                edit_text(&rl, text);
+                if rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+                    // Entering nothing closes the console
+                    let line = std::mem::take(text);
+                    if line.trim().is_empty() {
+                        console_prompt = None;
+                    } else {
+                        console_lines.push((format!("> {line}"), Color::WHITE));
+                        match scripts.run(&mut scene, &line, camera.target) {
+                            Ok(report) => {
+                                console_lines.extend(
+                                    report.output.into_iter().map(|line| (line, Color::WHITE)),
+                                );
+                                console_lines.extend(
+                                    report
+                                        .mismatches
+                                        .iter()
+                                        .map(|mismatch| (mismatch.to_string(), Color::ORANGE)),
+                                );
+                                violations.extend(report.violations);
+                            }
+                            Err(error) => console_lines.push((error.to_string(), Color::ORANGE)),
+                        }
+                        let excess = console_lines.len().saturating_sub(CONSOLE_LINES);
+                        console_lines.drain(..excess);
+                        history.clear(&scene);
+                        diagnostics = None;
+                        record_waveforms(&mut waveforms, &history, &scene);
+                    }
+                }
+            } else if !typing && rl.is_key_pressed(KeyboardKey::KEY_GRAVE) {
+                console_prompt = Some(String::new());
+            }
+        }
+
+        let mouse_world_pos = rl.get_screen_to_world2D(
+            Vector2 {
+                x: rl.get_mouse_x() as _,
+                y: rl.get_mouse_y() as _,
+            },
+            camera,
+        );
+
+        // Labels
+        {
+            if let Some((target, text)) = &mut label_prompt {
+                edit_text(&rl, text);
+                if rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+                    // Entering nothing removes the label
+                    let label = (!text.is_empty()).then(|| text.clone());
+                    match *target {
+                        LabelTarget::Component(id) if scene.get_component(id).typ.is_tunnel() => {
+                            scene.rename_tunnel(id, label.unwrap_or_default());
+                            history.clear(&scene);
+                            diagnostics = None;
+                            record_waveforms(&mut waveforms, &history, &scene);
+                        }
+                        LabelTarget::Component(id) => match &mut scene.get_component_mut(id).typ {
+                            ComponentType::InputPort { name, .. }
+                            | ComponentType::OutputPort { name, .. } => {
+                                *name = label.unwrap_or_default();
+                                diagnostics = None;
+                            }
+                            _ => scene.get_component_mut(id).labels.component = label,
+                        },
+                        LabelTarget::Pin(pin) => scene.set_pin_label(pin, label),
+                    }
+                    label_prompt = None;
+                }
+            } else if !typing && rl.is_key_pressed(KeyboardKey::KEY_L) {
+                // Tunnels and ports are renamed instead, since their name is what they show
+                label_prompt = match get_pin_at(&scene, mouse_world_pos) {
+                    Some(pin) => Some((
+                        LabelTarget::Pin(pin),
+                        scene.get_pin_label(pin).unwrap_or_default().to_string(),
+                    )),
+                    None => get_component_at(&scene, mouse_world_pos).map(|id| {
+                        let component = scene.get_component(id);
+                        let text = match &component.typ {
+                            ComponentType::Tunnel { name, .. }
+                            | ComponentType::InputPort { name, .. }
+                            | ComponentType::OutputPort { name, .. } => name.clone(),
+                            _ => component.labels.component.clone().unwrap_or_default(),
+                        };
+                        (LabelTarget::Component(id), text)
+                    }),
+                };
+            }
+        }
+
+        // Ports
+        if !typing {
+            if let Some(id) = get_component_at(&scene, mouse_world_pos) {
+                let component = scene.get_component(id);
+                let width = component.get_inputs().len() + component.get_outputs().len();
+                let new_width = if rl.is_key_pressed(KeyboardKey::KEY_RIGHT_BRACKET) {
+                    width + 1
+                } else if rl.is_key_pressed(KeyboardKey::KEY_LEFT_BRACKET) {
+                    width - 1
+                } else {
+                    width
+                };
+                let step = if rl.is_key_pressed(KeyboardKey::KEY_UP) {
+                    Some(1)
+                } else if rl.is_key_pressed(KeyboardKey::KEY_DOWN) {
+                    Some(u64::MAX)
+                } else {
+                    None
+                };
+                match &component.typ {
+                    ComponentType::InputPort { .. } | ComponentType::OutputPort { .. }
+                        if new_width != width && (1..=MAX_PORT_WIDTH).contains(&new_width) =>
+                    {
+                        scene.set_port_width(id, new_width);
+                        history.clear(&scene);
+                        diagnostics = None;
+                        record_waveforms(&mut waveforms, &history, &scene);
+                    }
+                    // The value wraps around within the bits the port has.
+                    // It is part of the state, so the change is recorded as a tick that can be stepped back over
+                    ComponentType::InputPort { value, .. } if step.is_some() => {
+                        let mask = u64::MAX >> (u64::BITS as usize - width);
+                        let value = value.wrapping_add(step.unwrap()) & mask;
+                        if let ComponentType::InputPort { value: old, .. } =
+                            &mut scene.get_component_mut(id).typ
+                        {
+                            *old = value;
+                        }
+                        violations.extend(history.update(&mut scene).violations);
+                        record_waveforms(&mut waveforms, &history, &scene);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // Time travel
+        if !typing {
+            if rl.is_key_pressed(KeyboardKey::KEY_SPACE) {
+                paused = !paused;
+                update_time = 0.0;
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_LEFT) {
+                paused = true;
+                history.step_back(&mut scene);
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_RIGHT) {
+                paused = true;
+                if !history.step_forward(&mut scene) {
+                    violations.extend(history.update(&mut scene).violations);
+                    record_waveforms(&mut waveforms, &history, &scene);
+                }
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_HOME) {
+                paused = true;
+                history.jump_to(&mut scene, history.oldest_tick());
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_END) {
+                history.jump_to(&mut scene, history.latest_tick());
+            }
+        }
+        {
+            if rl.is_mouse_button_down(MouseButton::MOUSE_LEFT_BUTTON)
+                && matches!(selected, Selected::Nothing)
+                && timeline.check_collision_point_rec(rl.get_mouse_position())
+            {
+                paused = true;
+                let t = (rl.get_mouse_x() as f32 - timeline.x) / timeline.width;
+                let recorded = history.latest_tick() - history.oldest_tick();
+                history.jump_to(
+                    &mut scene,
+                    history.oldest_tick() + (t * recorded as f32).round() as usize,
+                );
+            }
+        }
+
+        // Asserts
+        {
+            violation_flashes.retain(|_, time| {
+                *time -= ts;
+                *time > 0.0
+            });
+            for violation in violations {
+                violation_flashes.insert(violation.component, VIOLATION_FLASH_TIME);
+                last_violation = Some(violation);
+            }
+        }
+
+        // Truth table
+        if !typing {
+            if rl.is_key_pressed(KeyboardKey::KEY_T) {
+                truth_table = if truth_table.is_some() {
+                    None
+                } else {
+                    // Watched inputs that nothing is connected to can be set, so they become the inputs of the table
+                    let (inputs, outputs): (Vec<_>, Vec<_>) = waveforms
+                        .get_signals()
+                        .iter()
+                        .map(|&(_, pin)| pin)
+                        .partition(|&pin| match pin {
+                            Pin::Input(id, index) => scene.get_driver(id, index).is_none(),
+                            Pin::Output(..) => false,
+                        });
+                    Some(TruthTable::generate(
+                        &scene,
+                        &inputs,
+                        &outputs,
+                        TRUTH_TABLE_MAX_TICKS,
+                    ))
+                };
+            }
+            if let Some(Ok(truth_table)) = &truth_table {
+                if rl.is_key_pressed(KeyboardKey::KEY_C) {
+                    rl.set_clipboard_text(&truth_table.to_csv()).unwrap();
+                }
+                if rl.is_key_pressed(KeyboardKey::KEY_M) {
+                    rl.set_clipboard_text(&truth_table.to_markdown()).unwrap();
+                }
+            }
+        }
+
+        // Saving
+        let ctrl = rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL)
+            || rl.is_key_down(KeyboardKey::KEY_RIGHT_CONTROL);
+        if !typing && ctrl && rl.is_key_pressed(KeyboardKey::KEY_S) {
+            // Netlists are saved as scenes next to where the editor was started instead of being overwritten
+            let save_path = path
+                .as_deref()
+                .filter(|path| path.ends_with(".json"))
+                .unwrap_or("scene.json");
+            let json = serde_json::to_string_pretty(&scene).unwrap();
+            if let Err(error) = std::fs::write(save_path, json) {
+                eprintln!("Could not save {save_path}: {error}");
+            }
+        }
+
+        // Movement
+        if !typing && !ctrl {
             let mut move_direction = Vector2 { x: 0.0, y: 0.0 };
             if rl.is_key_down(KeyboardKey::KEY_W) {
                 move_direction.y -= 1.0;
@@ -90,22 +453,133 @@ fn main() {
             camera.target += move_direction.normalized() * 300.0 * ts;
         }
 
-        let mouse_world_pos = rl.get_screen_to_world2D(
-            Vector2 {
-                x: rl.get_mouse_x() as _,
-                y: rl.get_mouse_y() as _,
-            },
-            camera,
-        );
+        // Spawning
+        if !typing && !ctrl {
+            let typ = if rl.is_key_pressed(KeyboardKey::KEY_ONE) {
+                Some(ComponentType::Not {
+                    input: Input { state: false },
+                    output: None,
+                })
+            } else if rl.is_key_pressed(KeyboardKey::KEY_TWO) {
+                Some(ComponentType::Or {
+                    inputs: [Input { state: false }, Input { state: false }],
+                    output: None,
+                })
+            } else if rl.is_key_pressed(KeyboardKey::KEY_THREE) {
+                Some(ComponentType::Delay {
+                    input: Input { state: false },
+                    output: None,
+                    state_last_frame: false,
+                })
+            } else if rl.is_key_pressed(KeyboardKey::KEY_FOUR) {
+                Some(ComponentType::Assert {
+                    inputs: vec![Input { state: false }],
+                    condition: AssertCondition::AlwaysHigh,
+                })
+            } else if rl.is_key_pressed(KeyboardKey::KEY_FIVE) {
+                Some(ComponentType::Assert {
+                    inputs: vec![Input { state: false }],
+                    condition: AssertCondition::NeverHigh,
+                })
+            } else if rl.is_key_pressed(KeyboardKey::KEY_SIX) {
+                Some(ComponentType::Assert {
+                    inputs: vec![Input { state: false }; 2],
+                    condition: AssertCondition::Equal,
+                })
+            } else if rl.is_key_pressed(KeyboardKey::KEY_SEVEN) {
+                Some(ComponentType::Tunnel {
+                    name: String::new(),
+                    input: Input { state: false },
+                    output: None,
+                })
+            } else if rl.is_key_pressed(KeyboardKey::KEY_EIGHT) {
+                Some(ComponentType::InputPort {
+                    name: String::new(),
+                    outputs: vec![None],
+                    value: 0,
+                })
+            } else if rl.is_key_pressed(KeyboardKey::KEY_NINE) {
+                Some(ComponentType::OutputPort {
+                    name: String::new(),
+                    inputs: vec![Input { state: false }],
+                })
+            } else {
+                None
+            };
+            if let Some(typ) = typ {
+                let needs_name = typ.is_tunnel() || typ.is_input_port() || typ.is_output_port();
+                let id = scene.add_component(Component {
+                    typ,
+                    position: mouse_world_pos,
+                    labels: Default::default(),
+                });
+                // Tunnels and ports are only useful once they have a name
+                if needs_name {
+                    label_prompt = Some((LabelTarget::Component(id), String::new()));
+                }
+                history.clear(&scene);
+                diagnostics = None;
+                record_waveforms(&mut waveforms, &history, &scene);
+            }
+        }
+
+        // Waveforms
+        let waveform_panel = {
+            let height = waveforms.get_signals().len() as f32 * WAVEFORM_ROW_HEIGHT;
+            Rectangle {
+                x: timeline.x,
+                y: timeline.y - 32.0 - height,
+                width: timeline.width,
+                height,
+            }
+        };
+        let waveform_plot_x = waveform_panel.x + WAVEFORM_NAME_WIDTH;
+        let waveform_start = {
+            let visible_ticks =
+                ((waveform_panel.width - WAVEFORM_NAME_WIDTH) / waveform_tick_width) as usize;
+            waveforms
+                .end_tick()
+                .saturating_sub(visible_ticks)
+                .max(waveforms.first_tick())
+        };
+        {
+            if rl.is_mouse_button_pressed(MouseButton::MOUSE_MIDDLE_BUTTON) {
+                if let Some(pin) = get_pin_at(&scene, mouse_world_pos) {
+                    if waveforms.is_watched(pin) {
+                        waveforms.unwatch(pin);
+                    } else {
+                        waveforms.watch(&scene, pin);
+                    }
+                }
+            }
+            if waveform_panel.check_collision_point_rec(rl.get_mouse_position()) {
+                waveform_tick_width = (waveform_tick_width
+                    * 1.25f32.powf(rl.get_mouse_wheel_move()))
+                .clamp(1.0, 40.0);
+                if rl.is_mouse_button_down(MouseButton::MOUSE_LEFT_BUTTON)
+                    && matches!(selected, Selected::Nothing)
+                    && rl.get_mouse_x() as f32 >= waveform_plot_x
+                {
+                    paused = true;
+                    let tick = waveform_start
+                        + ((rl.get_mouse_x() as f32 - waveform_plot_x) / waveform_tick_width)
+                            as usize;
+                    history.jump_to(&mut scene, tick);
+                }
+            }
+        }
         selected = match selected {
             Selected::Nothing => {
-                if rl.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON) {
+                if rl.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON)
+                    && !timeline.check_collision_point_rec(rl.get_mouse_position())
+                    && !waveform_panel.check_collision_point_rec(rl.get_mouse_position())
+                {
                     (&scene)
                         .into_iter()
                         .rev()
                         .find_map(|id| {
                             let component = scene.get_component(id);
-                            let size = get_component_size(component);
+                            let size = component.get_size();
                             let position = component.position - (size * 0.5);
                             if (Rectangle {
                                 x: position.x,
@@ -165,6 +639,9 @@ fn main() {
                                     .state = false;
                             }
                             scene.get_component_mut(id).get_outputs_mut()[index] = None;
+                            history.clear(&scene);
+                            diagnostics = None;
+                            record_waveforms(&mut waveforms, &history, &scene);
                             break;
                         }
                     }
@@ -178,6 +655,7 @@ fn main() {
                 offset,
             } => {
                 if rl.is_mouse_button_released(MouseButton::MOUSE_LEFT_BUTTON) {
+                    diagnostics = None;
                     Selected::Nothing
                 } else {
                     scene.get_component_mut(id).position = rl.get_screen_to_world2D(
@@ -215,44 +693,14 @@ fn main() {
                             None
                         }
                     }) {
-                        if let Some(output) = scene.get_component_mut(id).get_outputs_mut()[index] {
-                            scene.get_component_mut(output.component).get_inputs_mut()
-                                [output.index]
-                                .state = false;
-                        }
-                        scene.get_component_mut(id).get_outputs_mut()[index] = Some(Output {
-                            component: input_id,
-                            index: input_index,
-                        });
-                        if scene.has_cyclic_dependency() {
-                            scene.get_component_mut(id).get_outputs_mut()[index] = None;
-                        } else {
-                            for other_id in &scene {
-                                if other_id != id {
-                                    let component = scene.get_component(other_id);
-                                    for output in component.get_outputs() {
-                                        if let Some(output) = *output {
-                                            if output.component == input_id
-                                                && output.index == input_index
-                                            {
-                                                if let Some(output) = scene
-                                                    .get_component_mut(other_id)
-                                                    .get_outputs_mut()[input_index]
-                                                {
-                                                    scene
-                                                        .get_component_mut(output.component)
-                                                        .get_inputs_mut()[output.index]
-                                                        .state = false;
-                                                }
-                                                scene
-                                                    .get_component_mut(other_id)
-                                                    .get_outputs_mut()[input_index] = None;
-                                                break;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
+                        // Connections that would make a loop without a delay are refused
+                        if scene
+                            .connect(Pin::Output(id, index), Pin::Input(input_id, input_index))
+                            .is_ok()
+                        {
+                            history.clear(&scene);
+                            diagnostics = None;
+                            record_waveforms(&mut waveforms, &history, &scene);
                         }
                     }
                     Selected::Nothing
@@ -265,6 +713,20 @@ fn main() {
             }
         };
 
+        // Design rules
+        let diagnostics = &*diagnostics.get_or_insert_with(|| {
+            let mut diagnostics = HashMap::<ComponentID, Vec<String>>::new();
+            for diagnostic in scene.lint() {
+                for id in diagnostic.get_components() {
+                    diagnostics
+                        .entry(id)
+                        .or_default()
+                        .push(diagnostic.to_string());
+                }
+            }
+            diagnostics
+        });
+
         // Drawing
         {
             let mut d = rl.begin_drawing(&thread);
@@ -274,11 +736,44 @@ fn main() {
             // Scene
             {
                 let mut d = d.begin_mode2D(camera);
+                // Hovering over a tunnel shows every other tunnel it is connected to
+                let hovered_tunnel = get_component_at(&scene, mouse_world_pos).and_then(|id| {
+                    match &scene.get_component(id).typ {
+                        ComponentType::Tunnel { name, .. } => Some(name.as_str()),
+                        _ => None,
+                    }
+                });
                 for id in &scene {
                     let component = scene.get_component(id);
 
-                    let size = get_component_size(component);
-                    d.draw_rectangle_v(component.position - (size * 0.5), size, Color::WHITE);
+                    let size = component.get_size();
+                    d.draw_rectangle_v(
+                        component.position - (size * 0.5),
+                        size,
+                        if violation_flashes.contains_key(&id) {
+                            Color::ORANGE
+                        } else if component.typ.is_tunnel() {
+                            Color::SKYBLUE
+                        } else if component.typ.is_input_port() || component.typ.is_output_port() {
+                            Color::BEIGE
+                        } else {
+                            Color::WHITE
+                        },
+                    );
+                    if let ComponentType::Tunnel { name, .. } = &component.typ {
+                        if hovered_tunnel == Some(name.as_str()) {
+                            d.draw_rectangle_lines_ex(
+                                Rectangle {
+                                    x: component.position.x - size.x * 0.5 - 4.0,
+                                    y: component.position.y - size.y * 0.5 - 4.0,
+                                    width: size.x + 8.0,
+                                    height: size.y + 8.0,
+                                },
+                                2,
+                                Color::YELLOW,
+                            );
+                        }
+                    }
 
                     for (i, (position, radius)) in
                         get_input_circles(component).into_iter().enumerate()
@@ -312,6 +807,16 @@ fn main() {
                             },
                         );
                     }
+                    for (pin, (position, radius)) in get_pins(id, component) {
+                        if waveforms.is_watched(pin) {
+                            d.draw_circle_lines(
+                                position.x as i32,
+                                position.y as i32,
+                                radius + 3.0,
+                                Color::YELLOW,
+                            );
+                        }
+                    }
                 }
                 for id in &scene {
                     let component = scene.get_component(id);
@@ -368,108 +873,423 @@ fn main() {
                 for id in &scene {
                     let component = scene.get_component(id);
 
-                    let name = component.get_name();
+                    let name = match &component.typ {
+                        ComponentType::InputPort { value, .. } => {
+                            format!("{} = {value}", component.get_label())
+                        }
+                        ComponentType::OutputPort { inputs, .. } => {
+                            let value = inputs
+                                .iter()
+                                .enumerate()
+                                .map(|(bit, input)| (input.state as u64) << bit)
+                                .sum::<u64>();
+                            format!("{} = {value}", component.get_label())
+                        }
+                        _ => component.get_label().to_string(),
+                    };
                     let font_size = 20;
-                    let width = measure_text(name, font_size);
+                    let width = measure_text(&name, font_size);
 
                     d.draw_text(
-                        name,
+                        &name,
                         component.position.x as i32 - width / 2,
                         component.position.y as i32 - font_size / 2,
                         font_size,
                         Color::DARKGRAY,
                     );
+
+                    // Pin labels go just inside the component, next to their pins
+                    const PIN_FONT_SIZE: i32 = 10;
+                    for (pin, (position, radius)) in get_pins(id, component) {
+                        if let Some(label) = scene.get_pin_label(pin) {
+                            let x = match pin {
+                                Pin::Input(..) => position.x + radius + 4.0,
+                                Pin::Output(..) => {
+                                    position.x
+                                        - radius
+                                        - 4.0
+                                        - measure_text(label, PIN_FONT_SIZE) as f32
+                                }
+                            };
+                            d.draw_text(
+                                label,
+                                x as i32,
+                                position.y as i32 - PIN_FONT_SIZE / 2,
+                                PIN_FONT_SIZE,
+                                Color::DARKGRAY,
+                            );
+                        }
+                    }
+
+                    // Components with warnings get a badge on their top right corner
+                    if diagnostics.contains_key(&id) {
+                        let size = component.get_size();
+                        let corner = component.position
+                            + Vector2 {
+                                x: size.x * 0.5,
+                                y: -size.y * 0.5,
+                            };
+                        d.draw_circle_v(corner, 10.0, Color::ORANGE);
+                        d.draw_text(
+                            "!",
+                            corner.x as i32 - 2,
+                            corner.y as i32 - 8,
+                            16,
+                            Color::BLACK,
+                        );
+                    }
                 }
             }
 
             d.draw_text(&format!("FPS: {}", 1.0 / ts), 12, 12, 20, Color::WHITE);
-        }
-    }
-}
 
-fn get_component_size(component: &Component) -> Vector2 {
-    Vector2 {
-        x: 150.0,
-        y: (component
-            .get_inputs()
-            .len()
-            .max(component.get_outputs().len())
-            * 50) as f32,
-    }
-}
+            // Synthesis
+            if let Some(text) = &expression_prompt {
+                d.draw_text(&format!("Expression: {text}_"), 12, 40, 20, Color::WHITE);
+            }
+            if let Some(error) = &synthesis_error {
+                d.draw_text(error, 12, 64, 20, Color::ORANGE);
+            }
 
-fn get_input_circles(component: &Component) -> Vec<(Vector2, f32)> {
-    let size = get_component_size(component);
-    let inputs = component.get_inputs();
-    if inputs.len() % 2 == 0 {
-        assert_eq!(inputs.len(), 2); // TODO: make this better
-        [
-            (
-                component.position
-                    - Vector2 {
-                        x: size.x / 3.0,
-                        y: -25.0,
+            // Labels
+            if let Some((target, text)) = &label_prompt {
+                let prompt = match *target {
+                    LabelTarget::Component(id) if scene.get_component(id).typ.is_tunnel() => {
+                        "Tunnel name"
+                    }
+                    LabelTarget::Component(id)
+                        if scene.get_component(id).typ.is_input_port()
+                            || scene.get_component(id).typ.is_output_port() =>
+                    {
+                        "Port name"
+                    }
+                    LabelTarget::Component(_) => "Label",
+                    LabelTarget::Pin(_) => "Pin label",
+                };
+                d.draw_text(&format!("{prompt}: {text}_"), 12, 40, 20, Color::WHITE);
+            }
+
+            // Asserts
+            if let Some(violation) = &last_violation {
+                d.draw_text(&violation.to_string(), 12, 88, 20, Color::ORANGE);
+            }
+
+            // Design rules, listed next to the mouse for the component under it
+            if let Some(lines) =
+                get_component_at(&scene, mouse_world_pos).and_then(|id| diagnostics.get(&id))
+            {
+                const FONT_SIZE: i32 = 10;
+                const ROW_HEIGHT: i32 = 12;
+                let mouse = d.get_mouse_position();
+                let (x, y) = (mouse.x as i32 + 16, mouse.y as i32 + 16);
+                let width = lines
+                    .iter()
+                    .map(|line| measure_text(line, FONT_SIZE))
+                    .max()
+                    .unwrap_or(0);
+                d.draw_rectangle(
+                    x,
+                    y,
+                    width + 8,
+                    lines.len() as i32 * ROW_HEIGHT + 8,
+                    Color::BLACK,
+                );
+                for (i, line) in lines.iter().enumerate() {
+                    d.draw_text(
+                        line,
+                        x + 4,
+                        y + 4 + i as i32 * ROW_HEIGHT,
+                        FONT_SIZE,
+                        Color::ORANGE,
+                    );
+                }
+            }
+
+            // Console
+            if let Some(text) = &console_prompt {
+                const FONT_SIZE: i32 = 10;
+                const ROW_HEIGHT: i32 = 12;
+                let lines = console_lines.len() as i32 + 1;
+                d.draw_rectangle(
+                    12,
+                    112,
+                    WIDTH as i32 / 2,
+                    lines * ROW_HEIGHT + 8,
+                    Color::BLACK,
+                );
+                for (i, (line, color)) in console_lines.iter().enumerate() {
+                    d.draw_text(line, 16, 116 + i as i32 * ROW_HEIGHT, FONT_SIZE, *color);
+                }
+                d.draw_text(
+                    &format!("> {text}_"),
+                    16,
+                    116 + (lines - 1) * ROW_HEIGHT,
+                    FONT_SIZE,
+                    Color::WHITE,
+                );
+            }
+
+            // Waveforms
+            {
+                d.draw_rectangle_rec(waveform_panel, Color::BLACK);
+                let end = waveforms.end_tick();
+                for (i, (name, _)) in waveforms.get_signals().iter().enumerate() {
+                    let y = waveform_panel.y + i as f32 * WAVEFORM_ROW_HEIGHT;
+                    let high = y + 4.0;
+                    let low = y + WAVEFORM_ROW_HEIGHT - 4.0;
+                    d.draw_text(
+                        &format!(
+                            "{name} = {}",
+                            match waveforms.get_state(history.tick(), i) {
+                                Some(true) => "1",
+                                Some(false) => "0",
+                                None => "?",
+                            }
+                        ),
+                        waveform_panel.x as i32 + 4,
+                        y as i32 + 7,
+                        10,
+                        Color::WHITE,
+                    );
+                    for tick in waveform_start..end {
+                        if let Some(state) = waveforms.get_state(tick, i) {
+                            let x = waveform_plot_x
+                                + (tick - waveform_start) as f32 * waveform_tick_width;
+                            let color = if state { Color::GREEN } else { Color::RED };
+                            let level = if state { high } else { low };
+                            d.draw_line_v(
+                                Vector2 { x, y: level },
+                                Vector2 {
+                                    x: x + waveform_tick_width,
+                                    y: level,
+                                },
+                                color,
+                            );
+                            if tick > 0 && waveforms.get_state(tick - 1, i) == Some(!state) {
+                                d.draw_line_v(Vector2 { x, y: high }, Vector2 { x, y: low }, color);
+                            }
+                        }
+                    }
+                }
+                if (waveform_start..end).contains(&history.tick()) {
+                    let x = waveform_plot_x
+                        + ((history.tick() - waveform_start) as f32 + 0.5) * waveform_tick_width;
+                    d.draw_line_v(
+                        Vector2 {
+                            x,
+                            y: waveform_panel.y,
+                        },
+                        Vector2 {
+                            x,
+                            y: waveform_panel.y + waveform_panel.height,
+                        },
+                        Color::YELLOW,
+                    );
+                }
+            }
+
+            // Truth table
+            if let Some(truth_table) = &truth_table {
+                const FONT_SIZE: i32 = 10;
+                const ROW_HEIGHT: i32 = 12;
+                let lines = match truth_table {
+                    Ok(truth_table) => {
+                        let states = |states: &[bool]| {
+                            states
+                                .iter()
+                                .map(|&state| if state { "1" } else { "0" }.to_string())
+                                .collect::<Vec<_>>()
+                        };
+                        let mut lines = vec![truth_table
+                            .get_inputs()
+                            .iter()
+                            .chain(truth_table.get_outputs())
+                            .cloned()
+                            .collect::<Vec<_>>()];
+                        lines.extend(truth_table.get_rows().iter().map(|(inputs, outputs)| {
+                            let mut line = states(inputs);
+                            line.extend(states(outputs));
+                            line
+                        }));
+                        lines
+                    }
+                    Err(error) => vec![vec![error.to_string()]],
+                };
+                let widths = lines[0]
+                    .iter()
+                    .map(|name| measure_text(name, FONT_SIZE) + 8)
+                    .collect::<Vec<_>>();
+                let max_lines = ((HEIGHT as i32 - 80) / ROW_HEIGHT) as usize;
+                let width = widths.iter().sum::<i32>() + 8;
+                let x = WIDTH as i32 - 12 - width;
+                let shown = lines.len().min(max_lines);
+                let height = (shown + (shown < lines.len()) as usize) as i32 * ROW_HEIGHT + 8;
+                d.draw_rectangle(x, 12, width, height, Color::BLACK);
+                let input_count = truth_table
+                    .as_ref()
+                    .map_or(0, |truth_table| truth_table.get_inputs().len());
+                for (row, line) in lines.iter().take(shown).enumerate() {
+                    let mut cell_x = x + 4;
+                    for (i, (cell, &cell_width)) in line.iter().zip(&widths).enumerate() {
+                        d.draw_text(
+                            cell,
+                            cell_x + (cell_width - measure_text(cell, FONT_SIZE)) / 2,
+                            16 + row as i32 * ROW_HEIGHT,
+                            FONT_SIZE,
+                            if i < input_count {
+                                Color::SKYBLUE
+                            } else {
+                                Color::WHITE
+                            },
+                        );
+                        cell_x += cell_width;
+                    }
+                }
+                if shown < lines.len() {
+                    d.draw_text(
+                        &format!("{} more rows", lines.len() - shown),
+                        x + 4,
+                        16 + shown as i32 * ROW_HEIGHT,
+                        FONT_SIZE,
+                        Color::WHITE,
+                    );
+                }
+            }
+
+            // Timeline
+            {
+                d.draw_rectangle_rec(timeline, Color::GRAY);
+                let recorded = history.latest_tick() - history.oldest_tick();
+                let t = if recorded > 0 {
+                    (history.tick() - history.oldest_tick()) as f32 / recorded as f32
+                } else {
+                    1.0
+                };
+                d.draw_rectangle_v(
+                    Vector2 {
+                        x: timeline.x,
+                        y: timeline.y,
                     },
-                7.5,
-            ),
-            (
-                component.position
-                    - Vector2 {
-                        x: size.x / 3.0,
-                        y: 25.0,
+                    Vector2 {
+                        x: timeline.width * t,
+                        y: timeline.height,
                     },
-                7.5,
-            ),
-        ]
-        .into()
-    } else {
-        assert_eq!(inputs.len(), 1); // TODO: make this better
-        [(
-            component.position
-                - Vector2 {
-                    x: size.x / 3.0,
-                    y: 0.0,
-                },
-            7.5,
-        )]
-        .into()
+                    Color::LIGHTGRAY,
+                );
+                d.draw_rectangle_v(
+                    Vector2 {
+                        x: timeline.x + timeline.width * t - 2.0,
+                        y: timeline.y - 4.0,
+                    },
+                    Vector2 {
+                        x: 4.0,
+                        y: timeline.height + 8.0,
+                    },
+                    Color::WHITE,
+                );
+                d.draw_text(
+                    &format!(
+                        "Tick {} / {}{}",
+                        history.tick(),
+                        history.latest_tick(),
+                        if paused { " (Paused)" } else { "" }
+                    ),
+                    timeline.x as i32,
+                    timeline.y as i32 - 24,
+                    20,
+                    Color::WHITE,
+                );
+            }
+        }
     }
 }
 
+fn get_input_circles(component: &Component) -> Vec<(Vector2, f32)> {
+    let size = component.get_size();
+    get_pin_circles(component, component.get_inputs().len(), -size.x / 3.0)
+}
+
 fn get_output_circles(component: &Component) -> Vec<(Vector2, f32)> {
-    let size = get_component_size(component);
-    let outputs = component.get_outputs();
-    if outputs.len() % 2 == 0 {
-        assert_eq!(outputs.len(), 2); // TODO: make this better
-        [
-            (
-                component.position
-                    - Vector2 {
-                        x: -size.x / 3.0,
-                        y: -25.0,
-                    },
-                7.5,
-            ),
+    let size = component.get_size();
+    get_pin_circles(component, component.get_outputs().len(), size.x / 3.0)
+}
+
+// Pins are spread 50 apart down one side of the component, with the first at the bottom
+fn get_pin_circles(component: &Component, count: usize, x: f32) -> Vec<(Vector2, f32)> {
+    (0..count)
+        .map(|i| {
             (
                 component.position
-                    - Vector2 {
-                        x: -size.x / 3.0,
-                        y: 25.0,
+                    + Vector2 {
+                        x,
+                        y: ((count as f32 - 1.0) / 2.0 - i as f32) * 50.0,
                     },
                 7.5,
-            ),
-        ]
-        .into()
-    } else {
-        assert_eq!(outputs.len(), 1); // TODO: make this better
-        [(
-            component.position
-                - Vector2 {
-                    x: -size.x / 3.0,
-                    y: 0.0,
-                },
-            7.5,
-        )]
-        .into()
+            )
+        })
+        .collect()
+}
+
+// Types the characters pressed this frame into `text`
+fn edit_text(rl: &RaylibHandle, text: &mut String) {
+    loop {
+        // SAFETY: raylib has been initialised, and this only reads from its input queue
+        let c = unsafe { raylib::ffi::GetCharPressed() };
+        if c == 0 {
+            break;
+        }
+        text.extend(char::from_u32(c as u32));
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
+        text.pop();
     }
 }
+
+fn record_waveforms(waveforms: &mut Tracer, history: &History, scene: &Scene) {
+    waveforms.truncate(history.tick());
+    waveforms.sample(scene);
+}
+
+fn get_pins(id: ComponentID, component: &Component) -> Vec<(Pin, (Vector2, f32))> {
+    get_input_circles(component)
+        .into_iter()
+        .enumerate()
+        .map(|(index, circle)| (Pin::Input(id, index), circle))
+        .chain(
+            get_output_circles(component)
+                .into_iter()
+                .enumerate()
+                .map(|(index, circle)| (Pin::Output(id, index), circle)),
+        )
+        .collect()
+}
+
+// The component drawn on top at `position`
+fn get_component_at(scene: &Scene, position: Vector2) -> Option<ComponentID> {
+    scene.into_iter().rev().find(|&id| {
+        let component = scene.get_component(id);
+        let size = component.get_size();
+        let corner = component.position - (size * 0.5);
+        Rectangle {
+            x: corner.x,
+            y: corner.y,
+            width: size.x,
+            height: size.y,
+        }
+        .check_collision_point_rec(position)
+    })
+}
+
+fn get_pin_at(scene: &Scene, position: Vector2) -> Option<Pin> {
+    scene.into_iter().find_map(|id| {
+        get_pins(id, scene.get_component(id))
+            .into_iter()
+            .find_map(|(pin, (center, radius))| {
+                if check_collision_point_circle(position, center, radius) {
+                    Some(pin)
+                } else {
+                    None
+                }
+            })
+    })
+}
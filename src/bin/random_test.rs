@@ -1,56 +1,46 @@
 use logic_sim::*;
 
 fn main() {
-    let mut scene = Scene::new();
-    let not = scene.add_component(Component {
-        typ: ComponentType::Or {
-            inputs: [Input { state: false }, Input { state: false }],
-            output: None,
-        },
-        position: Default::default(),
-    });
-    let delay = scene.add_component(Component {
-        typ: ComponentType::Delay {
-            input: Input { state: true },
-            output: Some(Output {
-                component: not,
-                index: 1,
-            }),
-            state_last_frame: false,
-        },
-        position: Default::default(),
-    });
+    let mut builder = CircuitBuilder::new();
+    let d = builder.input("d");
+    let delayed = builder.delay(d, false);
+    let x = builder.input("x");
+    let or = builder.or(x, delayed);
+    builder.output("or", or);
+    let mut circuit = builder.build();
+    circuit.set_input("d", true);
+
     println!("Before Update:");
-    println!("{scene}");
+    println!("{}", circuit.scene);
     println!();
-    dbg!(scene.update());
+    dbg!(circuit.scene.update());
     println!("After Update 1:");
-    println!("{scene}");
+    println!("{}", circuit.scene);
     println!();
-    dbg!(scene.update());
+    dbg!(circuit.scene.update());
     println!("After Update 2:");
-    println!("{scene}");
+    println!("{}", circuit.scene);
     println!();
-    dbg!(scene.update());
+    dbg!(circuit.scene.update());
     println!("After Update 3:");
-    println!("{scene}");
+    println!("{}", circuit.scene);
     println!();
-    scene
-        .get_component_mut(delay)
-        .typ
-        .as_delay_mut()
-        .unwrap()
-        .0
-        .state = false;
-    dbg!(scene.update());
+    circuit.set_input("d", false);
+    dbg!(circuit.scene.update());
     println!("After Update 4:");
-    println!("{scene}");
+    println!("{}", circuit.scene);
     println!();
-    dbg!(scene.update());
+    dbg!(circuit.scene.update());
     println!("After Update 5:");
-    println!("{scene}");
+    println!("{}", circuit.scene);
     println!();
-    dbg!(scene.update());
+    dbg!(circuit.scene.update());
     println!("After Update 6:");
-    println!("{scene}");
+    println!("{}", circuit.scene);
+    println!();
+    circuit.set_input("d", true);
+    dbg!(circuit.scene.run_until_stable(100));
+    println!("After Running Until Stable:");
+    println!("{}", circuit.scene);
+    println!("or = {}", circuit.get_output("or"));
 }
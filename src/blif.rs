@@ -0,0 +1,583 @@
+use std::collections::{HashMap, HashSet};
+
+use derive_more::Display;
+use raylib::math::Vector2;
+
+use crate::{
+    auto_layout, Component, ComponentID, ComponentType, Expression, Input, Output, Pin, Scene,
+    Synthesis,
+};
+
+#[derive(Clone, Debug, Display)]
+pub enum BlifError {
+    #[display(fmt = "Line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[display(fmt = "Nothing drives the net {_0}")]
+    Undriven(String),
+    #[display(fmt = "The net {_0} depends on itself without going through a latch")]
+    CombinationalLoop(String),
+    #[display(fmt = "Too many gates, the logic has over a million operations")]
+    TooLarge,
+}
+
+/// A scene imported from a netlist, with a one bit port named after each of its primary inputs and outputs
+pub struct ImportedNetlist {
+    pub name: String,
+    pub scene: Scene,
+}
+
+struct Names {
+    inputs: Vec<String>,
+    cover: Vec<(String, bool)>,
+}
+
+struct Latch {
+    input: String,
+    output: String,
+    initial_state: bool,
+}
+
+/// The most operations the logic can have
+const MAX_OPERATIONS: usize = 1_000_000;
+
+/// How many nets deep logic can be built into a single expression before a net is given its own tunnels
+const MAX_DEPTH: usize = 64;
+
+/// Reads the first model of a Berkeley Logic Interchange Format file, as written by `yosys write_blif`.
+///
+/// Latches become delays, with unknown initial states starting off.
+/// Each output can only drive one input, so primary inputs, latches and nets read in several places reach their
+/// readers through tunnels named after the net
+pub fn import_blif(text: &str) -> Result<ImportedNetlist, BlifError> {
+    let mut name = String::new();
+    let mut primary_inputs = vec![];
+    let mut primary_outputs = vec![];
+    let mut definitions = HashMap::new();
+    let mut latches = vec![];
+
+    // Lines ending in a backslash carry on to the next one
+    let mut lines = vec![];
+    let mut continued: Option<(usize, String)> = None;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap();
+        let (number, mut joined) = continued.take().unwrap_or((i + 1, String::new()));
+        match line.trim_end().strip_suffix('\\') {
+            Some(line) => {
+                joined += line;
+                joined.push(' ');
+                continued = Some((number, joined));
+            }
+            None => {
+                joined += line;
+                lines.push((number, joined));
+            }
+        }
+    }
+    lines.extend(continued);
+
+    let mut current_names: Option<(String, Names)> = None;
+    for (line, text) in &lines {
+        let line = *line;
+        let tokens = text.split_whitespace().collect::<Vec<_>>();
+        let Some(&keyword) = tokens.first() else {
+            continue;
+        };
+        let parse_error = |message: String| BlifError::Parse { line, message };
+
+        if !keyword.starts_with('.') {
+            let Some((_, names)) = &mut current_names else {
+                return Err(parse_error(format!("Unexpected '{keyword}'")));
+            };
+            let (pattern, output) = match (names.inputs.len(), tokens.as_slice()) {
+                (0, &[output]) => ("", output),
+                (_, &[pattern, output]) => (pattern, output),
+                _ => {
+                    return Err(parse_error(
+                        "Expected an input pattern and an output".into(),
+                    ))
+                }
+            };
+            if pattern.len() != names.inputs.len()
+                || !pattern.chars().all(|c| matches!(c, '0' | '1' | '-'))
+            {
+                return Err(parse_error(format!(
+                    "'{pattern}' is not a pattern for {} inputs",
+                    names.inputs.len()
+                )));
+            }
+            let output = match output {
+                "0" => false,
+                "1" => true,
+                _ => return Err(parse_error(format!("'{output}' is not 0 or 1"))),
+            };
+            if names
+                .cover
+                .first()
+                .is_some_and(|&(_, other)| other != output)
+            {
+                return Err(parse_error("Rows must all have the same output".into()));
+            }
+            names.cover.push((pattern.to_string(), output));
+            continue;
+        }
+
+        if let Some((net, names)) = current_names.take() {
+            definitions.insert(net, names);
+        }
+        match keyword {
+            ".model" => {
+                if !name.is_empty() {
+                    break;
+                }
+                name = tokens.get(1).unwrap_or(&"top").to_string();
+            }
+            ".inputs" => primary_inputs.extend(tokens[1..].iter().map(|net| net.to_string())),
+            ".outputs" => primary_outputs.extend(tokens[1..].iter().map(|net| net.to_string())),
+            ".names" => {
+                let Some((output, inputs)) = tokens[1..].split_last() else {
+                    return Err(parse_error(".names needs an output".into()));
+                };
+                current_names = Some((
+                    output.to_string(),
+                    Names {
+                        inputs: inputs.iter().map(|net| net.to_string()).collect(),
+                        cover: vec![],
+                    },
+                ));
+            }
+            // Connections are buffers
+            ".conn" => {
+                let &[_, input, output] = tokens.as_slice() else {
+                    return Err(parse_error(".conn needs an input and an output".into()));
+                };
+                definitions.insert(
+                    output.to_string(),
+                    Names {
+                        inputs: vec![input.to_string()],
+                        cover: vec![("1".into(), true)],
+                    },
+                );
+            }
+            ".latch" => {
+                let (input, output, initial_state) = match tokens.as_slice() {
+                    &[_, input, output] | &[_, input, output, _, _] => (input, output, "3"),
+                    &[_, input, output, initial_state]
+                    | &[_, input, output, _, _, initial_state] => (input, output, initial_state),
+                    _ => return Err(parse_error(".latch needs an input and an output".into())),
+                };
+                latches.push(Latch {
+                    input: input.to_string(),
+                    output: output.to_string(),
+                    initial_state: initial_state == "1",
+                });
+            }
+            ".end" => break,
+            ".attr" | ".param" | ".cname" => {}
+            _ => return Err(parse_error(format!("{keyword} is not supported"))),
+        }
+    }
+    if let Some((net, names)) = current_names.take() {
+        definitions.insert(net, names);
+    }
+
+    let leaves = primary_inputs
+        .iter()
+        .chain(latches.iter().map(|latch| &latch.output))
+        .map(String::as_str)
+        .collect::<HashSet<_>>();
+    let roots = primary_outputs
+        .iter()
+        .chain(latches.iter().map(|latch| &latch.input))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    let (order, uses) = sort_nets(&definitions, &leaves, &roots)?;
+
+    // A net used more than once is built on its own and reaches its readers through tunnels, and so is one that would
+    // make its reader too deep. The rest are built into the net that reads them
+    let mut variables = leaves.clone();
+    let mut expressions = HashMap::<&str, (Expression, usize)>::new();
+    let mut shared = vec![];
+    for &net in &order {
+        let names = &definitions[net];
+        let mut depth = 0;
+        let inputs = names
+            .inputs
+            .iter()
+            .map(|input| match expressions.remove(input.as_str()) {
+                Some((expression, input_depth)) => {
+                    depth = depth.max(input_depth);
+                    expression
+                }
+                None => Expression::Variable(input.clone()),
+            })
+            .collect();
+        let expression = cover(names, inputs);
+        if uses.get(net).copied().unwrap_or(0) > 1 || depth + 1 > MAX_DEPTH {
+            variables.insert(net);
+            shared.push((net.to_string(), expression));
+        } else {
+            expressions.insert(net, (expression, depth + 1));
+        }
+    }
+    let mut outputs = roots
+        .iter()
+        .map(|&net| {
+            let expression = match variables.contains(net) {
+                true => Expression::Variable(net.to_string()),
+                false => expressions.remove(net).unwrap().0,
+            };
+            (net.to_string(), expression)
+        })
+        .collect::<Vec<_>>();
+    outputs.extend(shared);
+    if outputs
+        .iter()
+        .map(|(_, expression)| expression.operation_count())
+        .sum::<usize>()
+        > MAX_OPERATIONS
+    {
+        return Err(BlifError::TooLarge);
+    }
+
+    let (mut scene, synthesis) = Synthesis::new_scene(&outputs);
+    let mut components = synthesis.get_components().to_vec();
+    let readers = |net: &str| {
+        synthesis
+            .get_inputs()
+            .iter()
+            .find(|(name, _)| name == net)
+            .map_or(&[][..], |(_, pins)| pins)
+    };
+    for (latch, &(_, driver)) in latches
+        .iter()
+        .zip(&synthesis.get_outputs()[primary_outputs.len()..])
+    {
+        let delay = scene.add_component(Component {
+            typ: ComponentType::Delay {
+                input: Input { state: false },
+                output: None,
+                state_last_frame: latch.initial_state,
+            },
+            position: Vector2::zero(),
+            labels: Default::default(),
+        });
+        components.push(delay);
+        connect(&mut scene, driver, Some(Pin::Input(delay, 0)));
+        fan_out(
+            &mut scene,
+            &mut components,
+            &latch.output,
+            Pin::Output(delay, 0),
+            readers(&latch.output),
+        );
+    }
+    for (net, driver) in &synthesis.get_outputs()[roots.len()..] {
+        fan_out(&mut scene, &mut components, net, *driver, readers(net));
+    }
+    for net in &primary_inputs {
+        let port = scene.add_component(Component {
+            typ: ComponentType::InputPort {
+                name: net.clone(),
+                outputs: vec![None],
+                value: 0,
+            },
+            position: Vector2::zero(),
+            labels: Default::default(),
+        });
+        components.push(port);
+        fan_out(
+            &mut scene,
+            &mut components,
+            net,
+            Pin::Output(port, 0),
+            readers(net),
+        );
+    }
+    for (net, driver) in &synthesis.get_outputs()[..primary_outputs.len()] {
+        let port = scene.add_component(Component {
+            typ: ComponentType::OutputPort {
+                name: net.clone(),
+                inputs: vec![Input { state: false }],
+            },
+            position: Vector2::zero(),
+            labels: Default::default(),
+        });
+        components.push(port);
+        connect(&mut scene, *driver, Some(Pin::Input(port, 0)));
+    }
+    auto_layout(&mut scene, &components, Vector2::zero());
+
+    Ok(ImportedNetlist { name, scene })
+}
+
+// An output can only drive one input, so a net read in several places gets there through tunnels named after it
+fn fan_out(
+    scene: &mut Scene,
+    components: &mut Vec<ComponentID>,
+    net: &str,
+    driver: Pin,
+    readers: &[Pin],
+) {
+    if readers.len() <= 1 {
+        connect(scene, driver, readers.first().copied());
+        return;
+    }
+    let mut add_tunnel = |scene: &mut Scene| {
+        let tunnel = scene.add_component(Component {
+            typ: ComponentType::Tunnel {
+                name: net.to_string(),
+                input: Input { state: false },
+                output: None,
+            },
+            position: Vector2::zero(),
+            labels: Default::default(),
+        });
+        components.push(tunnel);
+        tunnel
+    };
+    let sender = add_tunnel(scene);
+    connect(scene, driver, Some(Pin::Input(sender, 0)));
+    for &reader in readers {
+        let receiver = add_tunnel(scene);
+        connect(scene, Pin::Output(receiver, 0), Some(reader));
+    }
+}
+
+// Sets where an output goes without checking for loops, which latches always break
+fn connect(scene: &mut Scene, output: Pin, input: Option<Pin>) {
+    let Pin::Output(id, index) = output else {
+        unreachable!()
+    };
+    scene.get_component_mut(id).get_outputs_mut()[index] = input.map(|input| {
+        let Pin::Input(component, index) = input else {
+            unreachable!()
+        };
+        Output { component, index }
+    });
+}
+
+// Every net the roots depend on that is not a leaf, with the nets each one reads coming before it, and how many
+// literals read each net
+fn sort_nets<'a>(
+    definitions: &'a HashMap<String, Names>,
+    leaves: &HashSet<&str>,
+    roots: &[&'a str],
+) -> Result<(Vec<&'a str>, HashMap<&'a str, usize>), BlifError> {
+    let mut order = vec![];
+    let mut uses = HashMap::<&str, usize>::new();
+    let mut visiting = HashSet::new();
+    let mut finished = HashSet::new();
+    for &root in roots {
+        *uses.entry(root).or_default() += 1;
+        // Each net on the stack is paired with the next of its inputs to look at
+        let mut stack = vec![(root, 0)];
+        while let Some((net, next)) = stack.pop() {
+            if next == 0 {
+                if leaves.contains(net) || finished.contains(net) {
+                    continue;
+                }
+                if !definitions.contains_key(net) {
+                    return Err(BlifError::Undriven(net.to_string()));
+                }
+                visiting.insert(net);
+            }
+            let names = &definitions[net];
+            let Some(input) = names.inputs.get(next) else {
+                visiting.remove(net);
+                finished.insert(net);
+                order.push(net);
+                continue;
+            };
+            if visiting.contains(input.as_str()) {
+                return Err(BlifError::CombinationalLoop(input.clone()));
+            }
+            // Each row that does not ignore the input reads it again
+            *uses.entry(input).or_default() += names
+                .cover
+                .iter()
+                .filter(|(pattern, _)| pattern.as_bytes()[next] != b'-')
+                .count()
+                .max(1);
+            stack.push((net, next + 1));
+            stack.push((input, 0));
+        }
+    }
+    Ok((order, uses))
+}
+
+// The expression for a net, given the expressions for each of its inputs
+fn cover(names: &Names, inputs: Vec<Expression>) -> Expression {
+    let products = names
+        .cover
+        .iter()
+        .map(|(pattern, _)| {
+            let mut literals = pattern
+                .chars()
+                .zip(&inputs)
+                .filter_map(|(c, input)| match c {
+                    '1' => Some(input.clone()),
+                    '0' => Some(Expression::Not(Box::new(input.clone()))),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            match literals.len() {
+                0 => Expression::Constant(true),
+                1 => literals.pop().unwrap(),
+                _ => Expression::And(literals),
+            }
+        })
+        .collect::<Vec<_>>();
+    let sum = match products.len() {
+        0 => Expression::Constant(false),
+        1 => products.into_iter().next().unwrap(),
+        _ => Expression::Or(products),
+    };
+    // A cover listing the rows that are off describes the inverse
+    match names.cover.first() {
+        Some((_, false)) => Expression::Not(Box::new(sum)),
+        _ => sum,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Stability;
+
+    const FULL_ADDER: &str = "\
+.model full_adder
+.inputs a b \\
+    cin
+.outputs sum cout
+.names a b cin sum
+100 1
+010 1
+001 1
+111 1
+.names a b cin cout
+11- 1
+1-1 1
+-11 1
+.end
+";
+
+    #[test]
+    fn imports_ports() {
+        let mut netlist = import_blif(FULL_ADDER).unwrap();
+        assert_eq!(netlist.name, "full_adder");
+        let names = netlist
+            .scene
+            .ports()
+            .into_iter()
+            .map(|port| port.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a", "b", "cin", "sum", "cout"]);
+
+        let scene = &mut netlist.scene;
+        for inputs in 0..8u64 {
+            for (index, name) in ["a", "b", "cin"].into_iter().enumerate() {
+                scene.set_port(name, inputs >> index & 1).unwrap();
+            }
+            assert!(matches!(
+                scene.run_until_stable(100),
+                Stability::Stable { .. }
+            ));
+            let total = inputs.count_ones() as u64;
+            assert_eq!(scene.get_port("sum").unwrap(), total & 1, "{inputs:03b}");
+            assert_eq!(scene.get_port("cout").unwrap(), total >> 1, "{inputs:03b}");
+        }
+    }
+
+    // A ripple carry adder split into two input gates the way a synthesis tool would write it
+    fn adder(bits: usize) -> String {
+        let names = |prefix: &str| {
+            (0..bits)
+                .map(|i| format!("{prefix}{i}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let mut blif = format!(
+            ".model adder\n.inputs {} {} c0\n.outputs {} c{bits}\n",
+            names("a"),
+            names("b"),
+            names("s")
+        );
+        for i in 0..bits {
+            let next = i + 1;
+            blif += &format!(
+                ".names a{i} b{i} p{i}\n10 1\n01 1\n\
+                 .names p{i} c{i} s{i}\n10 1\n01 1\n\
+                 .names a{i} b{i} g{i}\n11 1\n\
+                 .names p{i} c{i} t{i}\n11 1\n\
+                 .names g{i} t{i} c{next}\n1- 1\n-1 1\n"
+            );
+        }
+        blif + ".end\n"
+    }
+
+    #[test]
+    fn imports_wide_adders() {
+        const BITS: usize = 16;
+        let mut netlist = import_blif(&adder(BITS)).unwrap();
+        let scene = &mut netlist.scene;
+        // Shared nets are built once, so the size grows with the number of gates
+        assert!((&*scene).into_iter().count() < 60 * BITS);
+
+        let mut random = 0x2545f4914f6cdd1du64;
+        for _ in 0..20 {
+            random ^= random << 13;
+            random ^= random >> 7;
+            random ^= random << 17;
+            let (a, b, carry) = (random & 0xffff, random >> 16 & 0xffff, random >> 32 & 1);
+            for i in 0..BITS {
+                scene.set_port(&format!("a{i}"), a >> i & 1).unwrap();
+                scene.set_port(&format!("b{i}"), b >> i & 1).unwrap();
+            }
+            scene.set_port("c0", carry).unwrap();
+            assert!(matches!(
+                scene.run_until_stable(100),
+                Stability::Stable { .. }
+            ));
+            let sum = (0..BITS)
+                .map(|i| scene.get_port(&format!("s{i}")).unwrap() << i)
+                .sum::<u64>()
+                + (scene.get_port(&format!("c{BITS}")).unwrap() << BITS);
+            assert_eq!(sum, a + b + carry, "{a} + {b} + {carry}");
+        }
+    }
+
+    #[test]
+    fn imports_deep_chains() {
+        const LENGTH: usize = 20_000;
+        let mut blif = ".model chain\n.inputs n0\n.outputs y\n".to_string();
+        for i in 0..LENGTH {
+            blif += &format!(".names n{i} n{}\n0 1\n", i + 1);
+        }
+        blif += &format!(".names n{LENGTH} y\n1 1\n.end\n");
+        let mut netlist = import_blif(&blif).unwrap();
+        let scene = &mut netlist.scene;
+        scene.set_port("n0", 1).unwrap();
+        assert!(matches!(
+            scene.run_until_stable(10),
+            Stability::Stable { .. }
+        ));
+        assert_eq!(scene.get_port("y").unwrap(), 1);
+    }
+
+    #[test]
+    fn rejects_bad_netlists() {
+        let error = |blif: &str| import_blif(blif).err().unwrap().to_string();
+        assert_eq!(
+            error(".model m\n.inputs a\n.outputs y\n.names a x y\n11 1\n.end\n"),
+            "Nothing drives the net x"
+        );
+        assert_eq!(
+            error(".model m\n.outputs y\n.names x y\n1 1\n.names y x\n0 1\n.end\n"),
+            "The net y depends on itself without going through a latch"
+        );
+        assert_eq!(
+            error(".model m\n.inputs a\n.outputs y\n.names a y\n1 1 1\n"),
+            "Line 5: Expected an input pattern and an output"
+        );
+    }
+}
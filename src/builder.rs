@@ -0,0 +1,397 @@
+use raylib::math::Vector2;
+
+use crate::{
+    auto_layout, not_gate, or_gate, AssertCondition, Component, ComponentID, ComponentType, Input,
+    Output, Pin, Scene, MAX_PORT_WIDTH,
+};
+
+/// A signal in a [`CircuitBuilder`] that nothing reads yet.
+///
+/// An output can only drive one input, so a wire is used up when something reads it and cannot be copied.
+/// Calling [`CircuitBuilder::input`] again gives another wire for the same input
+///
+/// ```compile_fail
+/// let mut b = logic_sim::CircuitBuilder::new();
+/// let x = b.input("x");
+/// let n = b.not(x);
+/// let y = b.or(n, n);
+/// ```
+#[must_use]
+pub struct Wire(Source);
+
+enum Source {
+    Output(ComponentID, usize),
+    Input(usize),
+}
+
+/// The input of a delay made by [`CircuitBuilder::feedback`], to be driven once the signal it reads has been built
+#[must_use]
+pub struct Feedback(ComponentID);
+
+/// A scene made by a [`CircuitBuilder`], with the pins its named inputs and outputs ended up on
+pub struct Circuit {
+    pub scene: Scene,
+    /// Every input pin that reads each named input
+    pub inputs: Vec<(String, Vec<Pin>)>,
+    pub outputs: Vec<(String, Pin)>,
+}
+
+impl Circuit {
+    /// Sets every pin that reads the input called `name`
+    pub fn set_input(&mut self, name: &str, state: bool) {
+        let (_, pins) = self
+            .inputs
+            .iter()
+            .find(|(other, _)| other == name)
+            .unwrap_or_else(|| panic!("There is no input called {name}"));
+        for &pin in pins {
+            if let Pin::Input(id, index) = pin {
+                self.scene.get_component_mut(id).get_inputs_mut()[index].state = state;
+            }
+        }
+    }
+
+    pub fn get_output(&self, name: &str) -> bool {
+        let &(_, pin) = self
+            .outputs
+            .iter()
+            .find(|(other, _)| other == name)
+            .unwrap_or_else(|| panic!("There is no output called {name}"));
+        self.scene.get_pin_state(pin)
+    }
+}
+
+/// Builds a scene in code, connecting each gate to the wires it is given, like
+/// `let a = b.or(x, y); let n = b.not(a);`.
+///
+/// Components are laid out when the circuit is built, apart from any given a position with [`CircuitBuilder::place`]
+#[derive(Default)]
+pub struct CircuitBuilder {
+    scene: Scene,
+    inputs: Vec<(String, Vec<Pin>)>,
+    outputs: Vec<(String, Pin)>,
+    unplaced: Vec<ComponentID>,
+}
+
+impl CircuitBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// A wire for the input called `name`, which nothing in the circuit drives
+    pub fn input(&mut self, name: &str) -> Wire {
+        let index = match self.inputs.iter().position(|(other, _)| other == name) {
+            Some(index) => index,
+            None => {
+                self.inputs.push((name.to_string(), vec![]));
+                self.inputs.len() - 1
+            }
+        };
+        Wire(Source::Input(index))
+    }
+
+    pub fn not(&mut self, a: Wire) -> Wire {
+        self.gate(not_gate(), [a])
+    }
+
+    pub fn or(&mut self, a: Wire, b: Wire) -> Wire {
+        self.gate(or_gate(), [a, b])
+    }
+
+    pub fn nor(&mut self, a: Wire, b: Wire) -> Wire {
+        let or = self.or(a, b);
+        self.not(or)
+    }
+
+    /// Made from an `Or` of the inverted inputs
+    pub fn nand(&mut self, a: Wire, b: Wire) -> Wire {
+        let (a, b) = (self.not(a), self.not(b));
+        self.or(a, b)
+    }
+
+    pub fn and(&mut self, a: Wire, b: Wire) -> Wire {
+        let nand = self.nand(a, b);
+        self.not(nand)
+    }
+
+    /// `a` as it was on the tick before, starting at `initial_state`
+    pub fn delay(&mut self, a: Wire, initial_state: bool) -> Wire {
+        let (output, feedback) = self.feedback(initial_state);
+        self.close(feedback, a);
+        output
+    }
+
+    /// A delay whose input is connected later with [`CircuitBuilder::close`], for loops
+    pub fn feedback(&mut self, initial_state: bool) -> (Wire, Feedback) {
+        let id = self.add(ComponentType::Delay {
+            input: Input { state: false },
+            output: None,
+            state_last_frame: initial_state,
+        });
+        (Wire(Source::Output(id, 0)), Feedback(id))
+    }
+
+    pub fn close(&mut self, feedback: Feedback, a: Wire) {
+        self.connect(a, Pin::Input(feedback.0, 0));
+    }
+
+    /// Adds an assert that reads each of `wires`
+    pub fn assert(
+        &mut self,
+        condition: AssertCondition,
+        wires: impl IntoIterator<Item = Wire>,
+    ) -> ComponentID {
+        let wires = wires.into_iter().collect::<Vec<_>>();
+        assert!(!wires.is_empty(), "Asserts need at least one input");
+        let id = self.add(ComponentType::Assert {
+            inputs: vec![Input { state: false }; wires.len()],
+            condition,
+        });
+        for (index, wire) in wires.into_iter().enumerate() {
+            self.connect(wire, Pin::Input(id, index));
+        }
+        id
+    }
+
+    /// Adds an input port called `name`, with a wire for each of its bits, lowest bit first.
+    /// Unlike [`CircuitBuilder::input`], each bit can only be read once
+    pub fn input_port(&mut self, name: &str, width: usize) -> Vec<Wire> {
+        assert!(
+            (1..=MAX_PORT_WIDTH).contains(&width),
+            "Ports are 1 to {MAX_PORT_WIDTH} bits wide"
+        );
+        let id = self.add(ComponentType::InputPort {
+            name: name.to_string(),
+            outputs: vec![None; width],
+            value: 0,
+        });
+        (0..width)
+            .map(|index| Wire(Source::Output(id, index)))
+            .collect()
+    }
+
+    /// Adds an output port called `name` that reads each of `wires`, lowest bit first
+    pub fn output_port(
+        &mut self,
+        name: &str,
+        wires: impl IntoIterator<Item = Wire>,
+    ) -> ComponentID {
+        let wires = wires.into_iter().collect::<Vec<_>>();
+        assert!(
+            (1..=MAX_PORT_WIDTH).contains(&wires.len()),
+            "Ports are 1 to {MAX_PORT_WIDTH} bits wide"
+        );
+        let id = self.add(ComponentType::OutputPort {
+            name: name.to_string(),
+            inputs: vec![Input { state: false }; wires.len()],
+        });
+        for (index, wire) in wires.into_iter().enumerate() {
+            self.connect(wire, Pin::Input(id, index));
+        }
+        id
+    }
+
+    /// Names `a` as an output of the circuit, leaving it unconnected
+    pub fn output(&mut self, name: &str, a: Wire) {
+        let pin = match a.0 {
+            Source::Output(id, index) => Pin::Output(id, index),
+            // An input passes straight through an `Or` with nothing on its other input
+            Source::Input(_) => {
+                let buffer = self.gate(or_gate(), [a]);
+                let Source::Output(id, index) = buffer.0 else {
+                    unreachable!()
+                };
+                Pin::Output(id, index)
+            }
+        };
+        self.outputs.push((name.to_string(), pin));
+    }
+
+    /// Puts the component driving `a` at `position` instead of laying it out.
+    /// Inputs are not components, so placing their wires does nothing
+    pub fn place(&mut self, a: &Wire, position: Vector2) {
+        if let Source::Output(id, _) = a.0 {
+            self.scene.get_component_mut(id).position = position;
+            self.unplaced.retain(|&other| other != id);
+        }
+    }
+
+    pub fn build(mut self) -> Circuit {
+        auto_layout(&mut self.scene, &self.unplaced, Vector2::zero());
+        Circuit {
+            scene: self.scene,
+            inputs: self.inputs,
+            outputs: self.outputs,
+        }
+    }
+
+    fn add(&mut self, typ: ComponentType) -> ComponentID {
+        let id = self.scene.add_component(Component {
+            typ,
+            position: Vector2::zero(),
+            labels: Default::default(),
+        });
+        self.unplaced.push(id);
+        id
+    }
+
+    // Connects `wires` to the first inputs of a new gate, leaving any others with nothing connected
+    fn gate<const N: usize>(&mut self, typ: ComponentType, wires: [Wire; N]) -> Wire {
+        let id = self.add(typ);
+        for (index, wire) in wires.into_iter().enumerate() {
+            self.connect(wire, Pin::Input(id, index));
+        }
+        Wire(Source::Output(id, 0))
+    }
+
+    fn connect(&mut self, a: Wire, input: Pin) {
+        let Pin::Input(component, index) = input else {
+            unreachable!()
+        };
+        match a.0 {
+            Source::Output(id, output_index) => {
+                self.scene.get_component_mut(id).get_outputs_mut()[output_index] =
+                    Some(Output { component, index });
+            }
+            Source::Input(i) => self.inputs[i].1.push(input),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Diagnostic, Stability};
+
+    #[test]
+    fn reads_each_wire_once() {
+        let mut b = CircuitBuilder::new();
+        let x = b.input("x");
+        let y = b.input("y");
+        let and = b.and(x, y);
+        let x = b.input("x");
+        let nor = b.nor(and, x);
+        b.output("out", nor);
+        let circuit = b.build();
+
+        // Every use of an input gets its own pin, as nothing drives them
+        assert_eq!(circuit.inputs[0].1.len(), 2);
+        assert_eq!(circuit.inputs[1].1.len(), 1);
+        let drivers = circuit.scene.get_drivers();
+        for (_, pins) in &circuit.inputs {
+            for pin in pins {
+                let &Pin::Input(id, index) = pin else {
+                    panic!("{pin} is not an input")
+                };
+                assert!(!drivers.contains_key(&(id, index)));
+            }
+        }
+        assert!(!circuit
+            .scene
+            .lint()
+            .iter()
+            .any(|diagnostic| matches!(diagnostic, Diagnostic::MultipleDrivers { .. })));
+    }
+
+    #[test]
+    fn closes_feedback_loops() {
+        // A latch, which stays high once `set` has been
+        let mut b = CircuitBuilder::new();
+        let (q, feedback) = b.feedback(false);
+        let delay = feedback.0;
+        let set = b.input("set");
+        let or = b.or(q, set);
+        b.close(feedback, or);
+        let mut circuit = b.build();
+        let q = Pin::Output(delay, 0);
+
+        assert!(!circuit.scene.has_cyclic_dependency());
+        assert_eq!(
+            circuit.scene.run_until_stable(10),
+            Stability::Stable { ticks: 1 }
+        );
+        assert!(!circuit.scene.get_pin_state(q));
+        circuit.set_input("set", true);
+        assert!(matches!(
+            circuit.scene.run_until_stable(10),
+            Stability::Stable { .. }
+        ));
+        assert!(circuit.scene.get_pin_state(q));
+        circuit.set_input("set", false);
+        assert!(matches!(
+            circuit.scene.run_until_stable(10),
+            Stability::Stable { .. }
+        ));
+        assert!(circuit.scene.get_pin_state(q));
+
+        let mut b = CircuitBuilder::new();
+        let (q, feedback) = b.feedback(false);
+        let not = b.not(q);
+        b.close(feedback, not);
+        let mut circuit = b.build();
+        assert!(matches!(
+            circuit.scene.run_until_stable(10),
+            Stability::Oscillating { period: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn delays_start_in_their_initial_state() {
+        let mut b = CircuitBuilder::new();
+        let x = b.input("x");
+        let d = b.delay(x, true);
+        b.output("d", d);
+        let mut circuit = b.build();
+        assert!(circuit.get_output("d"));
+        circuit.scene.step(2);
+        assert!(!circuit.get_output("d"));
+    }
+
+    #[test]
+    fn keeps_the_order_of_inputs_and_outputs() {
+        let mut b = CircuitBuilder::new();
+        let x = b.input("x");
+        let w = b.input("w");
+        let x2 = b.input("x");
+        let or = b.or(x, w);
+        b.output("z", or);
+        b.output("y", x2);
+        // Port bits are lowest first, both ways
+        let bits = b.input_port("p", 3);
+        let q = b.output_port("q", bits);
+        let mut circuit = b.build();
+
+        let input_names = circuit.inputs.iter().map(|(name, _)| name);
+        assert!(input_names.eq(["x", "w"]));
+        let output_names = circuit.outputs.iter().map(|(name, _)| name);
+        assert!(output_names.eq(["z", "y"]));
+
+        circuit.scene.set_port("p", 0b011).unwrap();
+        circuit.scene.run_until_stable(10);
+        assert_eq!(circuit.scene.get_port("q"), Ok(0b011));
+        let states = (0..3)
+            .map(|index| circuit.scene.get_pin_state(Pin::Input(q, index)))
+            .collect::<Vec<_>>();
+        assert_eq!(states, [true, true, false]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Ports are 1 to 64 bits wide")]
+    fn rejects_empty_ports() {
+        let mut b = CircuitBuilder::new();
+        b.output_port("q", []);
+    }
+
+    #[test]
+    #[should_panic(expected = "Asserts need at least one input")]
+    fn rejects_empty_asserts() {
+        let mut b = CircuitBuilder::new();
+        b.assert(AssertCondition::AlwaysHigh, []);
+    }
+
+    #[test]
+    #[should_panic(expected = "There is no output called y")]
+    fn rejects_unknown_outputs() {
+        CircuitBuilder::new().build().get_output("y");
+    }
+}
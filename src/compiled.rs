@@ -0,0 +1,732 @@
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    ops::{BitOr, Not},
+};
+
+use crate::{ComponentID, ComponentType, Pin, Scene};
+
+/// The state of a net in a [`CompiledScene`], either a single `bool` or many independent simulations packed into bits
+pub trait Signal: Copy + PartialEq + Not<Output = Self> + BitOr<Output = Self> {
+    const LOW: Self;
+
+    fn splat(state: bool) -> Self;
+}
+
+impl Signal for bool {
+    const LOW: Self = false;
+
+    fn splat(state: bool) -> Self {
+        state
+    }
+}
+
+impl Signal for u64 {
+    const LOW: Self = 0;
+
+    fn splat(state: bool) -> Self {
+        if state {
+            u64::MAX
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Operation {
+    Not,
+    Or,
+}
+
+// The output of instruction `i` is always net `first_gate_net + i`, so only the operands need storing
+#[derive(Clone, Copy)]
+struct Instruction {
+    operation: Operation,
+    first_operand: usize,
+    operand_count: usize,
+}
+
+#[derive(Clone, Copy)]
+struct CompiledDelay {
+    component: ComponentID,
+    input: usize,
+    output: usize,
+}
+
+/// A [`Scene`] flattened into levelized instruction arrays.
+///
+/// Every signal lives in a net. Nets are laid out as undriven inputs and input port bits first, then delay outputs, then
+/// one net per gate in level order. Each tick the delays output the state they captured last tick,
+/// the gates are evaluated in order, and then the delays capture their settled inputs.
+#[derive(Clone)]
+pub struct CompiledScene<S: Signal = bool> {
+    instructions: Vec<Instruction>,
+    operands: Vec<usize>,
+    levels: Vec<usize>,
+    delays: Vec<CompiledDelay>,
+    delay_states: Vec<S>,
+    input_offsets: Vec<usize>,
+    input_nets: Vec<usize>,
+    output_offsets: Vec<usize>,
+    output_nets: Vec<usize>,
+    free_net_count: usize,
+    first_gate_net: usize,
+    nets: Vec<S>,
+}
+
+/// Simulates 64 independent sets of inputs at once, one per bit
+pub type BatchScene = CompiledScene<u64>;
+
+impl<S: Signal> CompiledScene<S> {
+    pub fn new(scene: &Scene) -> Self {
+        assert!(
+            !scene.has_cyclic_dependency(),
+            "There were cyclic connections"
+        );
+
+        let mut input_offsets = vec![];
+        let mut input_count = 0;
+        let mut output_offsets = vec![];
+        let mut output_count = 0;
+        let mut drivers = HashMap::new();
+        for id in scene {
+            let component = scene.get_component(id);
+            input_offsets.push(input_count);
+            input_count += component.get_inputs().len();
+            output_offsets.push(output_count);
+            output_count += component.get_outputs().len();
+            for (index, output) in component.get_outputs().iter().enumerate() {
+                if let Some(output) = output {
+                    drivers
+                        .entry((output.component.0, output.index))
+                        .or_insert((id.0, index));
+                }
+            }
+        }
+
+        // Levelize the gates, delays break every loop so they are not part of the ordering.
+        // Asserts and output ports drive nothing, so they are left out entirely and asserts are not checked
+        let is_gate = |id: usize| {
+            matches!(
+                scene.get_component(ComponentID(id)).typ,
+                ComponentType::Not { .. } | ComponentType::Or { .. } | ComponentType::Tunnel { .. }
+            )
+        };
+        // Gates read their own inputs, apart from tunnels, which are an `Or` of the inputs of every tunnel with their name
+        let mut tunnels = HashMap::<&str, Vec<(usize, usize)>>::new();
+        for id in scene {
+            if let ComponentType::Tunnel { name, .. } = &scene.get_component(id).typ {
+                tunnels.entry(name).or_default().push((id.0, 0));
+            }
+        }
+        let operand_pins = (0..input_offsets.len())
+            .map(|id| {
+                let component = scene.get_component(ComponentID(id));
+                match &component.typ {
+                    ComponentType::Tunnel { name, .. } => tunnels[name.as_str()].clone(),
+                    _ if is_gate(id) => (0..component.get_inputs().len())
+                        .map(|index| (id, index))
+                        .collect(),
+                    _ => vec![],
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut readers = HashMap::<(usize, usize), Vec<usize>>::new();
+        for (id, pins) in operand_pins.iter().enumerate() {
+            for &pin in pins {
+                readers.entry(pin).or_default().push(id);
+            }
+        }
+        let mut gate_levels = vec![0; input_offsets.len()];
+        let mut waiting_on = vec![0; input_offsets.len()];
+        for (pin, &(from, _)) in &drivers {
+            if is_gate(from) {
+                for &to in readers.get(pin).into_iter().flatten() {
+                    waiting_on[to] += 1;
+                }
+            }
+        }
+        let mut ready = (0..input_offsets.len())
+            .filter(|&id| is_gate(id) && waiting_on[id] == 0)
+            .collect::<Vec<_>>();
+        let mut gates = vec![];
+        while let Some(id) = ready.pop() {
+            gates.push((gate_levels[id], id));
+            for (index, output) in scene
+                .get_component(ComponentID(id))
+                .get_outputs()
+                .iter()
+                .enumerate()
+                .filter_map(|(index, output)| output.map(|output| (index, output)))
+            {
+                let pin = (output.component.0, output.index);
+                if drivers[&pin] != (id, index) {
+                    continue;
+                }
+                for &to in readers.get(&pin).into_iter().flatten() {
+                    gate_levels[to] = gate_levels[to].max(gate_levels[id] + 1);
+                    waiting_on[to] -= 1;
+                    if waiting_on[to] == 0 {
+                        ready.push(to);
+                    }
+                }
+            }
+        }
+        gates.sort_by_key(|&(level, id)| (level, id));
+
+        let mut free_net_count = 0;
+        let mut input_nets = vec![usize::MAX; input_count];
+        for id in scene {
+            for index in 0..scene.get_component(id).get_inputs().len() {
+                if !drivers.contains_key(&(id.0, index)) {
+                    input_nets[input_offsets[id.0] + index] = free_net_count;
+                    free_net_count += 1;
+                }
+            }
+        }
+
+        // Input ports are set from outside like undriven inputs, so their bits are free nets too
+        let mut output_nets = vec![usize::MAX; output_count];
+        for id in scene {
+            if let ComponentType::InputPort { outputs, .. } = &scene.get_component(id).typ {
+                for index in 0..outputs.len() {
+                    output_nets[output_offsets[id.0] + index] = free_net_count;
+                    free_net_count += 1;
+                }
+            }
+        }
+
+        let mut next_net = free_net_count;
+        let mut delays = vec![];
+        let mut delay_states = vec![];
+        for id in scene {
+            if let ComponentType::Delay {
+                state_last_frame, ..
+            } = scene.get_component(id).typ
+            {
+                output_nets[output_offsets[id.0]] = next_net;
+                delays.push(CompiledDelay {
+                    component: id,
+                    input: usize::MAX,
+                    output: next_net,
+                });
+                delay_states.push(S::splat(state_last_frame));
+                next_net += 1;
+            }
+        }
+
+        let first_gate_net = next_net;
+        let mut levels = vec![];
+        for (i, &(level, id)) in gates.iter().enumerate() {
+            while levels.len() <= level {
+                levels.push(i);
+            }
+            for index in 0..scene.get_component(ComponentID(id)).get_outputs().len() {
+                output_nets[output_offsets[id] + index] = first_gate_net + i;
+            }
+        }
+        levels.push(gates.len());
+
+        for (&(to, to_index), &(from, from_index)) in &drivers {
+            input_nets[input_offsets[to] + to_index] =
+                output_nets[output_offsets[from] + from_index];
+        }
+        for delay in &mut delays {
+            delay.input = input_nets[input_offsets[delay.component.0]];
+        }
+
+        let mut instructions = vec![];
+        let mut operands = vec![];
+        for &(_, id) in &gates {
+            let component = scene.get_component(ComponentID(id));
+            let operation = match component.typ {
+                ComponentType::Not { .. } => Operation::Not,
+                ComponentType::Or { .. } | ComponentType::Tunnel { .. } => Operation::Or,
+                ComponentType::Delay { .. }
+                | ComponentType::Assert { .. }
+                | ComponentType::InputPort { .. }
+                | ComponentType::OutputPort { .. } => unreachable!(),
+            };
+            instructions.push(Instruction {
+                operation,
+                first_operand: operands.len(),
+                operand_count: operand_pins[id].len(),
+            });
+            operands.extend(
+                operand_pins[id]
+                    .iter()
+                    .map(|&(to, index)| input_nets[input_offsets[to] + index]),
+            );
+        }
+
+        let mut nets = vec![S::LOW; first_gate_net + gates.len()];
+        for id in scene {
+            let component = scene.get_component(id);
+            for (index, input) in component.get_inputs().iter().enumerate() {
+                nets[input_nets[input_offsets[id.0] + index]] = S::splat(input.state);
+            }
+            if let ComponentType::InputPort { outputs, value, .. } = &component.typ {
+                for index in 0..outputs.len() {
+                    nets[output_nets[output_offsets[id.0] + index]] =
+                        S::splat(value & (1 << index) != 0);
+                }
+            }
+        }
+        for (delay, &state) in delays.iter().zip(&delay_states) {
+            if scene.get_component(delay.component).get_outputs()[0].is_none() {
+                nets[delay.output] = state;
+            }
+        }
+
+        let mut compiled = Self {
+            instructions,
+            operands,
+            levels,
+            delays,
+            delay_states,
+            input_offsets,
+            input_nets,
+            output_offsets,
+            output_nets,
+            free_net_count,
+            first_gate_net,
+            nets,
+        };
+        // Gates with nothing connected to their output have no state in the scene, so work it out
+        for (i, &(_, id)) in gates.iter().enumerate() {
+            if scene
+                .get_component(ComponentID(id))
+                .get_outputs()
+                .iter()
+                .all(|output| output.is_none())
+            {
+                compiled.nets[first_gate_net + i] = compiled.evaluate(i);
+            }
+        }
+        compiled
+    }
+
+    fn evaluate(&self, instruction: usize) -> S {
+        evaluate(self.instructions[instruction], &self.operands, &self.nets)
+    }
+
+    pub fn gate_count(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    pub fn get_input_state(&self, id: ComponentID, index: usize) -> S {
+        self.nets[self.input_nets[self.input_offsets[id.0] + index]]
+    }
+
+    pub fn set_input_state(&mut self, id: ComponentID, index: usize, state: S) {
+        self.set_pin_state(Pin::Input(id, index), state);
+    }
+
+    /// Sets an input that is not connected to an output, or a bit of an input port
+    pub fn set_pin_state(&mut self, pin: Pin, state: S) {
+        let net = match pin {
+            Pin::Input(id, index) => self.input_nets[self.input_offsets[id.0] + index],
+            Pin::Output(id, index) => self.output_nets[self.output_offsets[id.0] + index],
+        };
+        assert!(
+            net < self.free_net_count,
+            "Only inputs that are not connected to an output and input ports can be set"
+        );
+        self.nets[net] = state;
+    }
+
+    pub fn get_output_state(&self, id: ComponentID, index: usize) -> S {
+        self.nets[self.output_nets[self.output_offsets[id.0] + index]]
+    }
+
+    pub fn get_pin_state(&self, pin: Pin) -> S {
+        match pin {
+            Pin::Input(id, index) => self.get_input_state(id, index),
+            Pin::Output(id, index) => self.get_output_state(id, index),
+        }
+    }
+
+    /// Runs one tick, returning whether any signal changed
+    pub fn update(&mut self) -> bool {
+        let mut changed = self.output_delays();
+        for level in 0..self.level_count() {
+            let (instructions, operands, inputs, outputs) = self.split_level(level);
+            changed |= evaluate_gates(instructions, operands, inputs, outputs);
+        }
+        changed | self.capture_delays()
+    }
+
+    fn output_delays(&mut self) -> bool {
+        let mut changed = false;
+        for (delay, &state) in self.delays.iter().zip(&self.delay_states) {
+            changed |= self.nets[delay.output] != state;
+            self.nets[delay.output] = state;
+        }
+        changed
+    }
+
+    fn capture_delays(&mut self) -> bool {
+        let mut changed = false;
+        for (delay, state) in self.delays.iter().zip(&mut self.delay_states) {
+            changed |= *state != self.nets[delay.input];
+            *state = self.nets[delay.input];
+        }
+        changed
+    }
+
+    // Gates in a level only read nets from earlier levels, so those can be shared while the level's own nets are written
+    fn split_level(&mut self, level: usize) -> (&[Instruction], &[usize], &[S], &mut [S]) {
+        let (start, end) = (self.levels[level], self.levels[level + 1]);
+        let (inputs, outputs) = self.nets.split_at_mut(self.first_gate_net + start);
+        (
+            &self.instructions[start..end],
+            &self.operands,
+            inputs,
+            &mut outputs[..end - start],
+        )
+    }
+}
+
+impl<S: Signal + Send + Sync> CompiledScene<S> {
+    /// Same as [`CompiledScene::update`], but splits the gates of each level between `threads` threads.
+    /// Levels too small to be worth splitting are still evaluated on the calling thread
+    pub fn update_parallel(&mut self, threads: NonZeroUsize) -> bool {
+        const MIN_GATES_PER_THREAD: usize = 4096;
+
+        let mut changed = self.output_delays();
+        for level in 0..self.level_count() {
+            let (instructions, operands, inputs, outputs) = self.split_level(level);
+
+            let chunk_size = instructions
+                .len()
+                .div_ceil(threads.get())
+                .max(MIN_GATES_PER_THREAD);
+            if chunk_size >= instructions.len() {
+                changed |= evaluate_gates(instructions, operands, inputs, outputs);
+                continue;
+            }
+            changed |= std::thread::scope(|scope| {
+                let mut chunks = instructions
+                    .chunks(chunk_size)
+                    .zip(outputs.chunks_mut(chunk_size));
+                let (first_instructions, first_outputs) = chunks.next().unwrap();
+                let handles = chunks
+                    .map(|(instructions, outputs)| {
+                        scope.spawn(move || evaluate_gates(instructions, operands, inputs, outputs))
+                    })
+                    .collect::<Vec<_>>();
+                let changed = evaluate_gates(first_instructions, operands, inputs, first_outputs);
+                handles
+                    .into_iter()
+                    .fold(changed, |changed, handle| changed | handle.join().unwrap())
+            });
+        }
+        changed | self.capture_delays()
+    }
+}
+
+fn evaluate<S: Signal>(instruction: Instruction, operands: &[usize], nets: &[S]) -> S {
+    let Instruction {
+        operation,
+        first_operand,
+        operand_count,
+    } = instruction;
+    let operands = &operands[first_operand..first_operand + operand_count];
+    match operation {
+        Operation::Not => !nets[operands[0]],
+        Operation::Or => operands
+            .iter()
+            .fold(S::LOW, |state, &net| state | nets[net]),
+    }
+}
+
+fn evaluate_gates<S: Signal>(
+    instructions: &[Instruction],
+    operands: &[usize],
+    inputs: &[S],
+    outputs: &mut [S],
+) -> bool {
+    let mut changed = false;
+    for (&instruction, output) in instructions.iter().zip(outputs) {
+        let state = evaluate(instruction, operands, inputs);
+        changed |= *output != state;
+        *output = state;
+    }
+    changed
+}
+
+impl CompiledScene<bool> {
+    /// Copies the state of every input, delay and input port back into `scene`, which must be the scene this was compiled from
+    pub fn write_to_scene(&self, scene: &mut Scene) {
+        for id in &*scene {
+            let offset = self.input_offsets[id.0];
+            let output_offset = self.output_offsets[id.0];
+            let component = scene.get_component_mut(id);
+            for (index, input) in component.get_inputs_mut().iter_mut().enumerate() {
+                input.state = self.nets[self.input_nets[offset + index]];
+            }
+            if let ComponentType::InputPort { outputs, value, .. } = &mut component.typ {
+                *value = (0..outputs.len())
+                    .map(|index| {
+                        (self.nets[self.output_nets[output_offset + index]] as u64) << index
+                    })
+                    .sum();
+            }
+        }
+        for (delay, &state) in self.delays.iter().zip(&self.delay_states) {
+            *scene
+                .get_component_mut(delay.component)
+                .typ
+                .as_delay_mut()
+                .unwrap()
+                .2 = state;
+        }
+    }
+}
+
+impl BatchScene {
+    /// Runs every combination of `inputs` through the circuit 64 at a time, calling `f` with the index of the first
+    /// combination in the batch and the state of each of `outputs`, where bit `i` is for combination `first + i`.
+    /// Combination `n` sets `inputs[i]` high when bit `i` of `n` is set, and bits past the last combination should be ignored.
+    /// Inputs can be undriven inputs or the bits of input ports
+    pub fn for_each_combination(
+        &mut self,
+        inputs: &[Pin],
+        outputs: &[Pin],
+        mut f: impl FnMut(usize, &[u64]),
+    ) {
+        const LANE_PATTERNS: [u64; 6] = [
+            0xAAAA_AAAA_AAAA_AAAA,
+            0xCCCC_CCCC_CCCC_CCCC,
+            0xF0F0_F0F0_F0F0_F0F0,
+            0xFF00_FF00_FF00_FF00,
+            0xFFFF_0000_FFFF_0000,
+            0xFFFF_FFFF_0000_0000,
+        ];
+        assert!(
+            inputs.len() < usize::BITS as usize,
+            "Too many inputs to try every combination"
+        );
+        let mut states = vec![0; outputs.len()];
+        for first in (0..1usize << inputs.len()).step_by(64) {
+            for (i, &input) in inputs.iter().enumerate() {
+                let state = match LANE_PATTERNS.get(i) {
+                    Some(&pattern) => pattern,
+                    None => u64::splat(first & (1 << i) != 0),
+                };
+                self.set_pin_state(input, state);
+            }
+            self.update();
+            for (state, &output) in states.iter_mut().zip(outputs) {
+                *state = self.get_pin_state(output);
+            }
+            f(first, &states);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Component, Input, Output};
+
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    // A scene of gates, delays and tunnels, and the inputs nothing drives. Gates only drive components after them and
+    // delays can drive anything. Tunnels next to each other share a name and never drive a tunnel with their name, so
+    // each name acts like a single gate and there are no loops
+    fn random_scene(random: &mut Random, component_count: usize) -> (Scene, Vec<Pin>) {
+        let mut scene = Scene::new();
+        let mut name = 0;
+        let ids = (0..component_count)
+            .map(|_| {
+                let input = Input { state: false };
+                let typ = match random.below(8) {
+                    0..=1 => ComponentType::Not {
+                        input,
+                        output: None,
+                    },
+                    2..=4 => ComponentType::Or {
+                        inputs: [input; 2],
+                        output: None,
+                    },
+                    5 => ComponentType::Delay {
+                        input,
+                        output: None,
+                        state_last_frame: random.below(2) == 0,
+                    },
+                    _ => ComponentType::Tunnel {
+                        name: format!("t{name}"),
+                        input,
+                        output: None,
+                    },
+                };
+                if !typ.is_tunnel() {
+                    name += 1;
+                }
+                scene.add_component(Component {
+                    typ,
+                    position: Default::default(),
+                    labels: Default::default(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let tunnel_name = |scene: &Scene, id: ComponentID| match &scene.get_component(id).typ {
+            ComponentType::Tunnel { name, .. } => Some(name.clone()),
+            _ => None,
+        };
+        let mut free_inputs = vec![];
+        let mut delays = vec![];
+        for &id in ids.iter().rev() {
+            let name = tunnel_name(&scene, id);
+            let candidates = (0..free_inputs.len())
+                .filter(|&i| {
+                    let (component, _) = free_inputs[i];
+                    name.is_none() || tunnel_name(&scene, component) != name
+                })
+                .collect::<Vec<_>>();
+            if scene.get_component(id).typ.is_delay() {
+                delays.push(id);
+            } else if !candidates.is_empty() && random.below(10) != 0 {
+                let (component, index) =
+                    free_inputs.swap_remove(candidates[random.below(candidates.len())]);
+                scene.get_component_mut(id).get_outputs_mut()[0] =
+                    Some(Output { component, index });
+            }
+            for index in 0..scene.get_component(id).get_inputs().len() {
+                free_inputs.push((id, index));
+            }
+        }
+        for id in delays {
+            if !free_inputs.is_empty() {
+                let (component, index) = free_inputs.swap_remove(random.below(free_inputs.len()));
+                scene.get_component_mut(id).get_outputs_mut()[0] =
+                    Some(Output { component, index });
+            }
+        }
+
+        let free_inputs = free_inputs
+            .into_iter()
+            .map(|(id, index)| Pin::Input(id, index))
+            .collect();
+        (scene, free_inputs)
+    }
+
+    fn assert_same_inputs(scene: &Scene, compiled: &CompiledScene, tick: usize) {
+        for id in scene {
+            for (index, input) in scene.get_component(id).get_inputs().iter().enumerate() {
+                assert_eq!(
+                    input.state,
+                    compiled.get_input_state(id, index),
+                    "Input {index} of component {id} differs on tick {tick}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn matches_scene() {
+        let mut random = Random(0x2545F4914F6CDD1D);
+        for _ in 0..50 {
+            let (mut scene, free_inputs) = random_scene(&mut random, 40);
+            let mut compiled = CompiledScene::new(&scene);
+            for tick in 0..20 {
+                for _ in 0..3 {
+                    let Pin::Input(id, index) = free_inputs[random.below(free_inputs.len())] else {
+                        unreachable!()
+                    };
+                    let state = random.below(2) == 0;
+                    scene.get_component_mut(id).get_inputs_mut()[index].state = state;
+                    compiled.set_input_state(id, index, state);
+                }
+                scene.update();
+                compiled.update();
+                assert_same_inputs(&scene, &compiled, tick);
+            }
+        }
+    }
+
+    #[test]
+    fn batch_matches_single() {
+        const LANES: [usize; 4] = [0, 1, 37, 63];
+
+        let mut random = Random(0x9E3779B97F4A7C15);
+        for _ in 0..20 {
+            let (scene, free_inputs) = random_scene(&mut random, 40);
+            let mut batch = BatchScene::new(&scene);
+            let mut singles = LANES.map(|_| CompiledScene::<bool>::new(&scene));
+            for tick in 0..20 {
+                for _ in 0..3 {
+                    let pin = free_inputs[random.below(free_inputs.len())];
+                    let states = random.next();
+                    batch.set_pin_state(pin, states);
+                    for (single, lane) in singles.iter_mut().zip(LANES) {
+                        single.set_pin_state(pin, states & (1 << lane) != 0);
+                    }
+                }
+                batch.update();
+                for single in &mut singles {
+                    single.update();
+                }
+                for id in &scene {
+                    for index in 0..scene.get_component(id).get_inputs().len() {
+                        let states = batch.get_input_state(id, index);
+                        for (single, lane) in singles.iter().zip(LANES) {
+                            assert_eq!(
+                                states & (1 << lane) != 0,
+                                single.get_input_state(id, index),
+                                "Input {index} of component {id} differs in lane {lane} on tick {tick}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_matches_scene() {
+        let mut random = Random(0xD1B54A32D192ED03);
+        let (mut scene, free_inputs) = random_scene(&mut random, 20_000);
+        let mut compiled = CompiledScene::new(&scene);
+        // Levels of up to 4096 gates are evaluated on the calling thread
+        assert!(compiled
+            .levels
+            .windows(2)
+            .any(|level| level[1] - level[0] > 4096));
+
+        let threads = NonZeroUsize::new(4).unwrap();
+        for tick in 0..10 {
+            for _ in 0..100 {
+                let Pin::Input(id, index) = free_inputs[random.below(free_inputs.len())] else {
+                    unreachable!()
+                };
+                let state = random.below(2) == 0;
+                scene.get_component_mut(id).get_inputs_mut()[index].state = state;
+                compiled.set_input_state(id, index, state);
+            }
+            scene.update();
+            compiled.update_parallel(threads);
+            assert_same_inputs(&scene, &compiled, tick);
+        }
+    }
+}
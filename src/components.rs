@@ -1,22 +1,72 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
 use derive_more::{Display, IsVariant};
 use enum_as_inner::EnumAsInner;
+use serde::{Deserialize, Serialize};
 
 use crate::ComponentID;
 
-#[derive(Clone, Copy, Display)]
+#[derive(Clone, Copy, Display, Serialize, Deserialize)]
 #[display(fmt = "{}", "if *state { \"on\" } else { \"off\" }")]
 pub struct Input {
     pub state: bool,
 }
 
-#[derive(Clone, Copy, Display)]
+#[derive(Clone, Copy, Display, Serialize, Deserialize)]
 #[display(fmt = "Component {component}, Input {index}")]
 pub struct Output {
     pub component: ComponentID,
     pub index: usize,
 }
 
-#[derive(Clone, IsVariant, EnumAsInner)]
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash)]
+pub enum Pin {
+    #[display(fmt = "{_0}.{_1}")]
+    Input(ComponentID, usize),
+    #[display(fmt = "{_0}.out{_1}")]
+    Output(ComponentID, usize),
+}
+
+/// Reads pins written the way they are displayed, like `3.1` for the second input of component 3 and `3.out0` for its first output
+impl FromStr for Pin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("'{s}' is not a pin, pins look like 3.1 or 3.out0");
+        let (id, index) = s.split_once('.').ok_or_else(invalid)?;
+        let id = ComponentID(id.parse().map_err(|_| invalid())?);
+        match index.strip_prefix("out") {
+            Some(index) => Ok(Pin::Output(id, index.parse().map_err(|_| invalid())?)),
+            None => Ok(Pin::Input(id, index.parse().map_err(|_| invalid())?)),
+        }
+    }
+}
+
+/// What a [`ComponentType::Assert`] checks about its inputs on every tick
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AssertCondition {
+    #[display(fmt = "Always high")]
+    AlwaysHigh,
+    #[display(fmt = "Never high")]
+    NeverHigh,
+    /// Every input has the same state as the first
+    #[display(fmt = "Equal")]
+    Equal,
+}
+
+impl AssertCondition {
+    pub fn holds(self, inputs: &[Input]) -> bool {
+        match self {
+            AssertCondition::AlwaysHigh => inputs.iter().all(|input| input.state),
+            AssertCondition::NeverHigh => inputs.iter().all(|input| !input.state),
+            AssertCondition::Equal => inputs
+                .first()
+                .is_none_or(|first| inputs.iter().all(|input| input.state == first.state)),
+        }
+    }
+}
+
+#[derive(Clone, IsVariant, EnumAsInner, Serialize, Deserialize)]
 pub enum ComponentType {
     Not {
         input: Input,
@@ -31,12 +81,103 @@ pub enum ComponentType {
         output: Option<Output>,
         state_last_frame: bool,
     },
+    /// Has no outputs, and records a violation on every tick where its condition does not hold
+    Assert {
+        inputs: Vec<Input>,
+        condition: AssertCondition,
+    },
+    /// Every tunnel with the same name outputs whether any of their inputs are on, without a wire between them
+    Tunnel {
+        name: String,
+        input: Input,
+        output: Option<Output>,
+    },
+    /// One of the circuit's inputs, which drives its outputs with the bits of `value`, lowest bit first
+    InputPort {
+        name: String,
+        outputs: Vec<Option<Output>>,
+        value: u64,
+    },
+    /// One of the circuit's outputs, whose value is made from the bits on its inputs, lowest bit first
+    OutputPort { name: String, inputs: Vec<Input> },
+}
+
+/// Names given to a component and its pins, which are shown instead of its type and used to name its pins
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Labels {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub inputs: BTreeMap<usize, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub outputs: BTreeMap<usize, String>,
+}
+
+impl Labels {
+    pub fn is_empty(&self) -> bool {
+        self.component.is_none() && self.inputs.is_empty() && self.outputs.is_empty()
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Component {
     pub typ: ComponentType,
+    #[serde(with = "Vector2Def")]
     pub position: raylib::math::Vector2,
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "raylib::math::Vector2")]
+struct Vector2Def {
+    x: f32,
+    y: f32,
+}
+
+impl Display for Component {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.get_name())?;
+        match &self.typ {
+            ComponentType::Tunnel { name, .. } | ComponentType::OutputPort { name, .. } => {
+                write!(f, " {name}")?
+            }
+            ComponentType::InputPort { name, value, .. } => write!(f, " {name} = {value}")?,
+            _ => {}
+        }
+        if let Some(label) = &self.labels.component {
+            write!(f, " {label:?}")?;
+        }
+        writeln!(f)?;
+        let inputs = self.get_inputs();
+        if inputs.len() > 0 {
+            writeln!(f, "  Inputs:")?;
+            for (i, input) in inputs.iter().enumerate() {
+                write!(f, "    Input {i}")?;
+                if let Some(label) = self.labels.inputs.get(&i) {
+                    write!(f, " {label:?}")?;
+                }
+                writeln!(f, ": {input}")?;
+            }
+        }
+        let outputs = self.get_outputs();
+        if outputs.len() > 0 {
+            writeln!(f, "  Outputs:")?;
+            for (i, output) in outputs.iter().enumerate() {
+                write!(f, "    Output {i}")?;
+                if let Some(label) = self.labels.outputs.get(&i) {
+                    write!(f, " {label:?}")?;
+                }
+                write!(f, ": ")?;
+                if let Some(output) = output {
+                    writeln!(f, "{output}")?;
+                } else {
+                    writeln!(f, "Not Connected")?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Component {
@@ -55,6 +196,43 @@ impl Component {
                 output: _,
                 state_last_frame: _,
             } => "Delay",
+            ComponentType::Assert {
+                inputs: _,
+                condition: _,
+            } => "Assert",
+            ComponentType::Tunnel {
+                name: _,
+                input: _,
+                output: _,
+            } => "Tunnel",
+            ComponentType::InputPort {
+                name: _,
+                outputs: _,
+                value: _,
+            } => "InputPort",
+            ComponentType::OutputPort { name: _, inputs: _ } => "OutputPort",
+        }
+    }
+
+    /// How big the component is drawn, centred on its position, which is tall enough to fit its pins 50 apart
+    pub fn get_size(&self) -> raylib::math::Vector2 {
+        raylib::math::Vector2 {
+            x: 150.0,
+            y: (self.get_inputs().len().max(self.get_outputs().len()).max(1) * 50) as f32,
+        }
+    }
+
+    /// What the editor shows on the component, which is its label, the name of a tunnel or port or otherwise its type
+    pub fn get_label(&self) -> &str {
+        match (&self.labels.component, &self.typ) {
+            (Some(label), _) => label,
+            (
+                None,
+                ComponentType::Tunnel { name, .. }
+                | ComponentType::InputPort { name, .. }
+                | ComponentType::OutputPort { name, .. },
+            ) => name,
+            (None, _) => self.get_name(),
         }
     }
 
@@ -78,6 +256,21 @@ impl Component {
                 output: _,
                 state_last_frame: _,
             } => std::array::from_ref(input),
+            ComponentType::Assert {
+                inputs,
+                condition: _,
+            } => inputs,
+            ComponentType::Tunnel {
+                name: _,
+                input,
+                output: _,
+            } => std::array::from_ref(input),
+            ComponentType::InputPort {
+                name: _,
+                outputs: _,
+                value: _,
+            } => &[],
+            ComponentType::OutputPort { name: _, inputs } => inputs,
         }
     }
 
@@ -90,6 +283,21 @@ impl Component {
                 output: _,
                 state_last_frame: _,
             } => std::array::from_mut(input),
+            ComponentType::Assert {
+                inputs,
+                condition: _,
+            } => inputs,
+            ComponentType::Tunnel {
+                name: _,
+                input,
+                output: _,
+            } => std::array::from_mut(input),
+            ComponentType::InputPort {
+                name: _,
+                outputs: _,
+                value: _,
+            } => &mut [],
+            ComponentType::OutputPort { name: _, inputs } => inputs,
         }
     }
 
@@ -102,6 +310,21 @@ impl Component {
                 output,
                 state_last_frame: _,
             } => std::array::from_ref(output),
+            ComponentType::Assert {
+                inputs: _,
+                condition: _,
+            } => &[],
+            ComponentType::Tunnel {
+                name: _,
+                input: _,
+                output,
+            } => std::array::from_ref(output),
+            ComponentType::InputPort {
+                name: _,
+                outputs,
+                value: _,
+            } => outputs,
+            ComponentType::OutputPort { name: _, inputs: _ } => &[],
         }
     }
 
@@ -114,6 +337,21 @@ impl Component {
                 output,
                 state_last_frame: _,
             } => std::array::from_mut(output),
+            ComponentType::Assert {
+                inputs: _,
+                condition: _,
+            } => &mut [],
+            ComponentType::Tunnel {
+                name: _,
+                input: _,
+                output,
+            } => std::array::from_mut(output),
+            ComponentType::InputPort {
+                name: _,
+                outputs,
+                value: _,
+            } => outputs,
+            ComponentType::OutputPort { name: _, inputs: _ } => &mut [],
         }
     }
 }
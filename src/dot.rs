@@ -0,0 +1,50 @@
+use std::io::{self, Write};
+
+use crate::{ComponentType, Scene};
+
+impl Scene {
+    /// Writes the scene as a Graphviz graph, with a node per component and an edge per connection.
+    /// Edges are labelled with the input they go to and coloured by its state, and edges out of delays are
+    /// dashed so that loops stand out. Tunnels are not joined by edges, only named
+    pub fn write_dot(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "digraph scene {{")?;
+        writeln!(w, "    rankdir=LR;")?;
+        for id in self {
+            let component = self.get_component(id);
+            let shape = match component.typ {
+                ComponentType::Delay { .. } => "box",
+                ComponentType::Assert { .. } => "diamond",
+                ComponentType::Tunnel { .. } => "cds",
+                ComponentType::InputPort { .. } => "house",
+                ComponentType::OutputPort { .. } => "invhouse",
+                _ => "ellipse",
+            };
+            writeln!(
+                w,
+                "    n{id} [label=\"{} {id}\", shape={shape}];",
+                component.get_label().escape_default()
+            )?;
+        }
+        for id in self {
+            let component = self.get_component(id);
+            for output in component.get_outputs().iter().flatten() {
+                let state = self.get_component(output.component).get_inputs()[output.index].state;
+                writeln!(
+                    w,
+                    "    n{id} -> n{} [label=\"in{} = {}\", color={}{}];",
+                    output.component,
+                    output.index,
+                    state as u8,
+                    if state { "green" } else { "red" },
+                    if component.typ.is_delay() {
+                        ", style=dashed"
+                    } else {
+                        ""
+                    }
+                )?;
+            }
+        }
+        writeln!(w, "}}")?;
+        Ok(())
+    }
+}
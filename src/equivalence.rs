@@ -0,0 +1,475 @@
+use std::collections::HashMap;
+
+use derive_more::Display;
+
+use crate::{
+    sat::{Literal, Solver},
+    BatchScene, CompiledScene, ComponentID, ComponentType, Pin, Scene,
+};
+
+#[derive(Clone, Debug, Display)]
+pub enum EquivalenceError {
+    #[display(fmt = "{_0} is not an input that nothing is connected to or a bit of an input port")]
+    NotAnInput(Pin),
+    #[display(fmt = "{_0} depends on the state of a delay")]
+    Sequential(Pin),
+    #[display(fmt = "{_0} does not exist")]
+    NoSuchPin(Pin),
+}
+
+/// A set of inputs that the two scenes give different outputs for
+#[derive(Clone, Debug, Display)]
+#[display(
+    fmt = "Inputs {} give {} and {}",
+    "format_states(inputs)",
+    "format_states(first_outputs)",
+    "format_states(second_outputs)"
+)]
+pub struct Counterexample {
+    pub inputs: Vec<bool>,
+    pub first_outputs: Vec<bool>,
+    pub second_outputs: Vec<bool>,
+}
+
+#[derive(Clone, Debug, Display)]
+pub enum Equivalence {
+    #[display(fmt = "Equivalent")]
+    Equivalent,
+    #[display(fmt = "{_0}")]
+    Different(Counterexample),
+}
+
+impl Equivalence {
+    /// Up to this many inputs every combination is simulated, any more and a SAT solver proves equivalence instead
+    pub const MAX_EXHAUSTIVE_INPUTS: usize = 16;
+
+    /// Checks whether the outputs of two scenes always match when the matching inputs are set the same.
+    /// Each pair in `inputs` and `outputs` is a pin in `first` and the pin it corresponds to in `second`.
+    /// Inputs can also be the bits of input ports. Inputs and input ports that are not mapped keep their current state
+    pub fn check(
+        first: &Scene,
+        second: &Scene,
+        inputs: &[(Pin, Pin)],
+        outputs: &[(Pin, Pin)],
+    ) -> Result<Self, EquivalenceError> {
+        for (first_pin, second_pin) in inputs.iter().chain(outputs) {
+            for (scene, &pin) in [(first, first_pin), (second, second_pin)] {
+                if !scene.has_pin(pin) {
+                    return Err(EquivalenceError::NoSuchPin(pin));
+                }
+            }
+        }
+
+        let mut solver = Solver::new();
+        let mut encodings = [first, second].map(|scene| Encoding {
+            scene,
+            drivers: scene.get_drivers(),
+            inputs: HashMap::new(),
+            always_true: None,
+        });
+        for &(first_pin, second_pin) in inputs {
+            let variable = solver.new_variable();
+            for (encoding, pin) in encodings.iter_mut().zip([first_pin, second_pin]) {
+                let settable = match pin {
+                    Pin::Input(id, index) => !encoding.drivers.contains_key(&(id, index)),
+                    Pin::Output(id, _) => encoding.scene.get_component(id).typ.is_input_port(),
+                };
+                if !settable {
+                    return Err(EquivalenceError::NotAnInput(pin));
+                }
+                encoding.inputs.insert(pin, variable);
+            }
+        }
+
+        // The outputs differ if any of the pairs differ
+        let mut any_different = vec![];
+        for &(first_pin, second_pin) in outputs {
+            let a = encodings[0].encode(&mut solver, first_pin)?;
+            let b = encodings[1].encode(&mut solver, second_pin)?;
+            let different = solver.new_variable();
+            solver.add_clause(&[!different, a, b]);
+            solver.add_clause(&[!different, !a, !b]);
+            any_different.push(different);
+        }
+
+        let sides = |side: usize| {
+            let pick = |&(first, second): &(Pin, Pin)| [first, second][side];
+            (
+                inputs.iter().map(pick).collect::<Vec<_>>(),
+                outputs.iter().map(pick).collect::<Vec<_>>(),
+            )
+        };
+        let ((first_inputs, first_outputs), (second_inputs, second_outputs)) = (sides(0), sides(1));
+        let counterexample = if inputs.len() <= Self::MAX_EXHAUSTIVE_INPUTS {
+            find_difference(
+                [first, second],
+                [&first_inputs, &second_inputs],
+                [&first_outputs, &second_outputs],
+            )
+        } else {
+            solver.add_clause(&any_different);
+            solver.solve().then(|| {
+                first_inputs
+                    .iter()
+                    .map(|&pin| solver.value(encodings[0].inputs[&pin]))
+                    .collect()
+            })
+        };
+
+        Ok(match counterexample {
+            Some(input_states) => Equivalence::Different(Counterexample {
+                first_outputs: simulate(first, &first_inputs, &input_states, &first_outputs),
+                second_outputs: simulate(second, &second_inputs, &input_states, &second_outputs),
+                inputs: input_states,
+            }),
+            None => Equivalence::Equivalent,
+        })
+    }
+}
+
+// Tseitin encoding of the gates driving a pin
+struct Encoding<'a> {
+    scene: &'a Scene,
+    drivers: HashMap<(ComponentID, usize), Pin>,
+    inputs: HashMap<Pin, Literal>,
+    always_true: Option<Literal>,
+}
+
+impl Encoding<'_> {
+    // Inputs that are not mapped to a variable keep their current state
+    fn encode_input(&mut self, solver: &mut Solver, pin: Pin, state: bool) -> Literal {
+        if let Some(&variable) = self.inputs.get(&pin) {
+            return variable;
+        }
+        let always_true = *self.always_true.get_or_insert_with(|| {
+            let variable = solver.new_variable();
+            solver.add_clause(&[variable]);
+            variable
+        });
+        if state {
+            always_true
+        } else {
+            !always_true
+        }
+    }
+
+    fn encode(&mut self, solver: &mut Solver, pin: Pin) -> Result<Literal, EquivalenceError> {
+        match pin {
+            Pin::Input(id, index) => match self.drivers.get(&(id, index)) {
+                Some(&driver) => self.encode(solver, driver),
+                None => {
+                    let state = self.scene.get_component(id).get_inputs()[index].state;
+                    Ok(self.encode_input(solver, pin, state))
+                }
+            },
+            Pin::Output(id, index) => match &self.scene.get_component(id).typ {
+                ComponentType::Not { .. } => Ok(!self.encode(solver, Pin::Input(id, 0))?),
+                ComponentType::Or { inputs, .. } => {
+                    let inputs = (0..inputs.len()).map(|index| Pin::Input(id, index));
+                    self.encode_or(solver, inputs.collect())
+                }
+                ComponentType::Delay { .. } => Err(EquivalenceError::Sequential(pin)),
+                ComponentType::Assert { .. } => unreachable!("Asserts have no outputs"),
+                ComponentType::Tunnel { name, .. } => {
+                    let inputs = self.scene.get_tunnels(name).map(|id| Pin::Input(id, 0));
+                    self.encode_or(solver, inputs.collect())
+                }
+                ComponentType::InputPort { value, .. } => {
+                    let state = value & (1 << index) != 0;
+                    Ok(self.encode_input(solver, pin, state))
+                }
+                ComponentType::OutputPort { .. } => unreachable!("Output ports have no outputs"),
+            },
+        }
+    }
+
+    fn encode_or(
+        &mut self,
+        solver: &mut Solver,
+        inputs: Vec<Pin>,
+    ) -> Result<Literal, EquivalenceError> {
+        let output = solver.new_variable();
+        let mut clause = vec![!output];
+        for pin in inputs {
+            let input = self.encode(solver, pin)?;
+            solver.add_clause(&[!input, output]);
+            clause.push(input);
+        }
+        solver.add_clause(&clause);
+        Ok(output)
+    }
+}
+
+// Simulates every combination of the inputs 64 at a time, returning the first that gives different outputs
+fn find_difference(
+    scenes: [&Scene; 2],
+    inputs: [&[Pin]; 2],
+    outputs: [&[Pin]; 2],
+) -> Option<Vec<bool>> {
+    let [first_results, second_results] = [0, 1].map(|side| {
+        let mut batch = BatchScene::new(scenes[side]);
+        let mut results = vec![];
+        batch.for_each_combination(inputs[side], outputs[side], |_, states| {
+            results.extend_from_slice(states)
+        });
+        results
+    });
+
+    // With fewer than 6 inputs only the lowest lanes are real combinations
+    let input_count = inputs[0].len();
+    let lanes = if input_count >= 6 {
+        u64::MAX
+    } else {
+        (1 << (1 << input_count)) - 1
+    };
+    first_results
+        .iter()
+        .zip(&second_results)
+        .enumerate()
+        .find_map(|(i, (a, b))| {
+            let difference = (a ^ b) & lanes;
+            (difference != 0).then(|| {
+                let combination = i / outputs[0].len() * 64 + difference.trailing_zeros() as usize;
+                (0..input_count)
+                    .map(|i| combination & (1 << i) != 0)
+                    .collect()
+            })
+        })
+}
+
+fn simulate(scene: &Scene, inputs: &[Pin], states: &[bool], outputs: &[Pin]) -> Vec<bool> {
+    let mut compiled = CompiledScene::<bool>::new(scene);
+    for (&pin, &state) in inputs.iter().zip(states) {
+        compiled.set_pin_state(pin, state);
+    }
+    compiled.update();
+    outputs
+        .iter()
+        .map(|&pin| compiled.get_pin_state(pin))
+        .collect()
+}
+
+fn format_states(states: &[bool]) -> String {
+    states
+        .iter()
+        .map(|&state| if state { '1' } else { '0' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Circuit, CircuitBuilder, Stability, Wire};
+
+    // A circuit with inputs `x0`, `x1`... that are each read once, and an output `y`
+    fn circuit(
+        input_count: usize,
+        f: impl FnOnce(&mut CircuitBuilder, Vec<Wire>) -> Wire,
+    ) -> Circuit {
+        let mut b = CircuitBuilder::new();
+        let inputs = (0..input_count)
+            .map(|i| b.input(&format!("x{i}")))
+            .collect();
+        let y = f(&mut b, inputs);
+        b.output("y", y);
+        b.build()
+    }
+
+    type PinPairs = Vec<(Pin, Pin)>;
+
+    fn pins(first: &Circuit, second: &Circuit) -> (PinPairs, PinPairs) {
+        let inputs = first
+            .inputs
+            .iter()
+            .zip(&second.inputs)
+            .map(|((_, first), (_, second))| (first[0], second[0]))
+            .collect();
+        (inputs, vec![(first.outputs[0].1, second.outputs[0].1)])
+    }
+
+    fn simulate_circuit(circuit: &Circuit, states: &[bool]) -> bool {
+        let mut scene = circuit.scene.clone();
+        for ((_, pins), &state) in circuit.inputs.iter().zip(states) {
+            for &pin in pins {
+                if let Pin::Input(id, index) = pin {
+                    scene.get_component_mut(id).get_inputs_mut()[index].state = state;
+                }
+            }
+        }
+        assert!(matches!(
+            scene.run_until_stable(100),
+            Stability::Stable { .. }
+        ));
+        scene.get_pin_state(circuit.outputs[0].1)
+    }
+
+    fn check(first: &Circuit, second: &Circuit) -> Equivalence {
+        let (inputs, outputs) = pins(first, second);
+        let equivalence =
+            Equivalence::check(&first.scene, &second.scene, &inputs, &outputs).unwrap();
+        if let Equivalence::Different(counterexample) = &equivalence {
+            let first_output = simulate_circuit(first, &counterexample.inputs);
+            let second_output = simulate_circuit(second, &counterexample.inputs);
+            assert_ne!(first_output, second_output);
+            assert_eq!(counterexample.first_outputs, [first_output]);
+            assert_eq!(counterexample.second_outputs, [second_output]);
+        }
+        equivalence
+    }
+
+    fn pairs() -> Vec<(Circuit, Circuit, bool)> {
+        vec![
+            (
+                circuit(2, |b, x| {
+                    let [a, c] = x.try_into().ok().unwrap();
+                    b.nand(a, c)
+                }),
+                circuit(2, |b, x| {
+                    let [a, c] = x.try_into().ok().unwrap();
+                    let (a, c) = (b.not(a), b.not(c));
+                    b.or(a, c)
+                }),
+                true,
+            ),
+            (
+                circuit(3, |b, x| {
+                    let [a, c, d] = x.try_into().ok().unwrap();
+                    let c = b.or(c, d);
+                    b.or(a, c)
+                }),
+                circuit(3, |b, x| {
+                    let [a, c, d] = x.try_into().ok().unwrap();
+                    let a = b.or(a, c);
+                    b.or(a, d)
+                }),
+                true,
+            ),
+            (
+                circuit(4, |b, x| {
+                    let [a, c, d, e] = x.try_into().ok().unwrap();
+                    let (ac, de) = (b.and(a, c), b.and(d, e));
+                    b.or(ac, de)
+                }),
+                circuit(4, |b, x| {
+                    let [a, c, d, e] = x.try_into().ok().unwrap();
+                    let (ac, de) = (b.nand(a, c), b.nand(d, e));
+                    b.nand(ac, de)
+                }),
+                true,
+            ),
+            (
+                circuit(2, |b, x| {
+                    let [a, c] = x.try_into().ok().unwrap();
+                    b.and(a, c)
+                }),
+                circuit(2, |b, x| {
+                    let [a, c] = x.try_into().ok().unwrap();
+                    b.or(a, c)
+                }),
+                false,
+            ),
+            (
+                circuit(4, |b, x| {
+                    let [a, c, d, e] = x.try_into().ok().unwrap();
+                    let (ac, de) = (b.and(a, c), b.and(d, e));
+                    b.or(ac, de)
+                }),
+                circuit(4, |b, x| {
+                    let [a, c, d, e] = x.try_into().ok().unwrap();
+                    let (ad, ce) = (b.and(a, d), b.and(c, e));
+                    b.or(ad, ce)
+                }),
+                false,
+            ),
+        ]
+    }
+
+    #[test]
+    fn matches_exhaustive_simulation() {
+        for (first, second, equivalent) in pairs() {
+            let input_count = first.inputs.len();
+            let simulated = (0..1 << input_count).all(|combination| {
+                let states = (0..input_count)
+                    .map(|i| combination & (1 << i) != 0)
+                    .collect::<Vec<_>>();
+                simulate_circuit(&first, &states) == simulate_circuit(&second, &states)
+            });
+            assert_eq!(simulated, equivalent);
+            assert_eq!(
+                matches!(check(&first, &second), Equivalence::Equivalent),
+                equivalent
+            );
+        }
+    }
+
+    // Enough inputs that the SAT solver is used instead of simulating every combination
+    #[test]
+    fn proves_with_sat() {
+        const INPUTS: usize = Equivalence::MAX_EXHAUSTIVE_INPUTS + 4;
+
+        let chain =
+            |b: &mut CircuitBuilder, x: Vec<Wire>| x.into_iter().reduce(|a, c| b.or(a, c)).unwrap();
+        let tree = |b: &mut CircuitBuilder, mut x: Vec<Wire>| {
+            while x.len() > 1 {
+                let (a, c) = (x.remove(0), x.remove(0));
+                let a = b.nor(a, c);
+                x.push(b.not(a));
+            }
+            x.pop().unwrap()
+        };
+        let first = circuit(INPUTS, chain);
+        assert!(matches!(
+            check(&first, &circuit(INPUTS, tree)),
+            Equivalence::Equivalent
+        ));
+
+        // Differs only when every input but the last is off
+        let different = circuit(INPUTS, |b, mut x| {
+            let last = x.pop().unwrap();
+            let last = b.not(last);
+            x.push(last);
+            chain(b, x)
+        });
+        let Equivalence::Different(counterexample) = check(&first, &different) else {
+            panic!("The circuits are different");
+        };
+        assert!(counterexample.inputs[..INPUTS - 1]
+            .iter()
+            .all(|&state| !state));
+    }
+
+    #[test]
+    fn rejects_bad_pins() {
+        let first = circuit(2, |b, x| {
+            let [a, c] = x.try_into().ok().unwrap();
+            let a = b.delay(a, false);
+            b.or(a, c)
+        });
+        let (inputs, outputs) = pins(&first, &first);
+        let check = |inputs: &[(Pin, Pin)], outputs: &[(Pin, Pin)]| {
+            Equivalence::check(&first.scene, &first.scene, inputs, outputs)
+        };
+
+        assert!(matches!(
+            check(&inputs, &outputs),
+            Err(EquivalenceError::Sequential(_))
+        ));
+        let missing = Pin::Input(ComponentID(100), 0);
+        assert!(matches!(
+            check(&[(missing, missing)], &[]),
+            Err(EquivalenceError::NoSuchPin(pin)) if pin == missing
+        ));
+        let Pin::Output(id, _) = outputs[0].0 else {
+            unreachable!()
+        };
+        let past_the_end = Pin::Output(id, 5);
+        assert!(matches!(
+            check(&[], &[(past_the_end, past_the_end)]),
+            Err(EquivalenceError::NoSuchPin(pin)) if pin == past_the_end
+        ));
+        assert!(matches!(
+            check(&[(outputs[0].0, outputs[0].0)], &[]),
+            Err(EquivalenceError::NotAnInput(_))
+        ));
+    }
+}
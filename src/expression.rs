@@ -0,0 +1,603 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    iter::Peekable,
+    str::{CharIndices, FromStr},
+};
+
+use derive_more::Display;
+
+use crate::{ComponentType, Pin, Scene};
+
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+pub enum ExpressionError {
+    #[display(
+        fmt = "Too many variables, {_0} is more than the limit of {}",
+        "Expression::MAX_VARIABLES"
+    )]
+    TooManyVariables(usize),
+    #[display(fmt = "Unexpected '{character}' at position {position}")]
+    UnexpectedCharacter { character: char, position: usize },
+    #[display(fmt = "Unexpected end of expression")]
+    UnexpectedEnd,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Expression {
+    Constant(bool),
+    Variable(String),
+    Not(Box<Expression>),
+    And(Vec<Expression>),
+    Or(Vec<Expression>),
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fn write_operands(
+            f: &mut std::fmt::Formatter,
+            operands: &[Expression],
+            operator: &str,
+        ) -> std::fmt::Result {
+            for (i, operand) in operands.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {operator} ")?;
+                }
+                match operand {
+                    Expression::And(_) | Expression::Or(_) => write!(f, "({operand})")?,
+                    _ => write!(f, "{operand}")?,
+                }
+            }
+            Ok(())
+        }
+
+        match self {
+            Expression::Constant(value) => write!(f, "{}", *value as u8),
+            Expression::Variable(name) => write!(f, "{name}"),
+            Expression::Not(operand) => match **operand {
+                Expression::And(_) | Expression::Or(_) => write!(f, "!({operand})"),
+                _ => write!(f, "!{operand}"),
+            },
+            Expression::And(operands) => write_operands(f, operands, "&"),
+            Expression::Or(operands) => write_operands(f, operands, "|"),
+        }
+    }
+}
+
+/// Parses expressions like `(a & !b) | c`, where `!` binds tightest and `|` loosest.
+/// Variables are made of letters, digits and underscores, and `0` and `1` are constants
+impl FromStr for Expression {
+    type Err = ExpressionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            chars: s.char_indices().peekable(),
+        };
+        let expression = parser.parse_or()?;
+        match parser.next() {
+            Some((position, character)) => Err(ExpressionError::UnexpectedCharacter {
+                character,
+                position,
+            }),
+            None => Ok(expression),
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn next(&mut self) -> Option<(usize, char)> {
+        self.skip_whitespace();
+        self.chars.next()
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, ExpressionError> {
+        let mut operands = vec![self.parse_and()?];
+        while self.peek() == Some('|') {
+            self.next();
+            operands.push(self.parse_and()?);
+        }
+        Ok(if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else {
+            Expression::Or(operands)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, ExpressionError> {
+        let mut operands = vec![self.parse_not()?];
+        while self.peek() == Some('&') {
+            self.next();
+            operands.push(self.parse_not()?);
+        }
+        Ok(if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else {
+            Expression::And(operands)
+        })
+    }
+
+    fn parse_not(&mut self) -> Result<Expression, ExpressionError> {
+        match self.next() {
+            Some((_, '!')) => Ok(Expression::Not(Box::new(self.parse_not()?))),
+            Some((_, '(')) => {
+                let expression = self.parse_or()?;
+                match self.next() {
+                    Some((_, ')')) => Ok(expression),
+                    Some((position, character)) => Err(ExpressionError::UnexpectedCharacter {
+                        character,
+                        position,
+                    }),
+                    None => Err(ExpressionError::UnexpectedEnd),
+                }
+            }
+            Some((_, c)) if c.is_alphanumeric() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some((_, c)) = self
+                    .chars
+                    .next_if(|&(_, c)| c.is_alphanumeric() || c == '_')
+                {
+                    name.push(c);
+                }
+                Ok(match name.as_str() {
+                    "0" => Expression::Constant(false),
+                    "1" => Expression::Constant(true),
+                    _ => Expression::Variable(name),
+                })
+            }
+            Some((position, character)) => Err(ExpressionError::UnexpectedCharacter {
+                character,
+                position,
+            }),
+            None => Err(ExpressionError::UnexpectedEnd),
+        }
+    }
+}
+
+impl Expression {
+    /// The most variables an expression can have and still be minimised
+    pub const MAX_VARIABLES: usize = 16;
+
+    /// Works out the expression for a pin by walking back through the components driving it.
+    /// Inputs with nothing connected and the outputs of delays and input ports become variables named after their pins
+    pub fn extract(scene: &Scene, pin: Pin) -> Self {
+        let drivers = scene.get_drivers();
+        // The pins whose expressions a pin's expression is made from
+        let operands = |pin: Pin| match pin {
+            Pin::Input(id, index) => drivers.get(&(id, index)).copied().into_iter().collect(),
+            Pin::Output(id, _) => match &scene.get_component(id).typ {
+                ComponentType::Not { .. } => vec![Pin::Input(id, 0)],
+                ComponentType::Or { inputs, .. } => (0..inputs.len())
+                    .map(|index| Pin::Input(id, index))
+                    .collect(),
+                ComponentType::Tunnel { name, .. } => scene
+                    .get_tunnels(name)
+                    .map(|tunnel| Pin::Input(tunnel, 0))
+                    .collect(),
+                _ => vec![],
+            },
+        };
+
+        // A stack instead of recursion, so long chains of gates cannot overflow it. Each pin is worked out once, after
+        // its operands, and moved into the last expression that uses it instead of being copied
+        let mut order = vec![];
+        let mut visited = HashSet::new();
+        let mut stack = vec![(pin, false)];
+        while let Some((next, ready)) = stack.pop() {
+            if ready {
+                order.push(next);
+            } else if visited.insert(next) {
+                stack.push((next, true));
+                stack.extend(operands(next).into_iter().map(|operand| (operand, false)));
+            }
+        }
+        let mut uses = HashMap::<Pin, usize>::new();
+        for &next in &order {
+            for operand in operands(next) {
+                *uses.entry(operand).or_default() += 1;
+            }
+        }
+
+        let mut expressions = HashMap::<Pin, Expression>::new();
+        for next in order {
+            let mut operands = operands(next)
+                .into_iter()
+                .map(|operand| {
+                    let uses = uses.get_mut(&operand).unwrap();
+                    *uses -= 1;
+                    if *uses == 0 {
+                        expressions.remove(&operand).unwrap()
+                    } else {
+                        expressions[&operand].clone()
+                    }
+                })
+                .collect::<Vec<_>>();
+            let expression = match next {
+                Pin::Input(..) => operands
+                    .pop()
+                    .unwrap_or_else(|| Expression::Variable(scene.get_pin_name(next))),
+                Pin::Output(id, _) => match &scene.get_component(id).typ {
+                    ComponentType::Not { .. } => Expression::Not(Box::new(operands.pop().unwrap())),
+                    ComponentType::Or { .. } | ComponentType::Tunnel { .. } => {
+                        Expression::Or(operands)
+                    }
+                    ComponentType::Delay { .. } | ComponentType::InputPort { .. } => {
+                        Expression::Variable(scene.get_pin_name(next))
+                    }
+                    ComponentType::Assert { .. } => unreachable!("Asserts have no outputs"),
+                    ComponentType::OutputPort { .. } => {
+                        unreachable!("Output ports have no outputs")
+                    }
+                },
+            };
+            expressions.insert(next, expression);
+        }
+        expressions.remove(&pin).unwrap()
+    }
+
+    /// Every variable in the expression, sorted by name
+    pub fn variables(&self) -> Vec<String> {
+        fn collect<'a>(expression: &'a Expression, variables: &mut BTreeSet<&'a str>) {
+            match expression {
+                Expression::Constant(_) => {}
+                Expression::Variable(name) => {
+                    variables.insert(name);
+                }
+                Expression::Not(operand) => collect(operand, variables),
+                Expression::And(operands) | Expression::Or(operands) => {
+                    for operand in operands {
+                        collect(operand, variables);
+                    }
+                }
+            }
+        }
+
+        let mut variables = BTreeSet::new();
+        collect(self, &mut variables);
+        variables.into_iter().map(str::to_string).collect()
+    }
+
+    pub fn evaluate(&self, variables: &impl Fn(&str) -> bool) -> bool {
+        match self {
+            Expression::Constant(value) => *value,
+            Expression::Variable(name) => variables(name),
+            Expression::Not(operand) => !operand.evaluate(variables),
+            Expression::And(operands) => operands.iter().all(|operand| operand.evaluate(variables)),
+            Expression::Or(operands) => operands.iter().any(|operand| operand.evaluate(variables)),
+        }
+    }
+
+    /// The number of `Not`, `And` and `Or` operations in the expression
+    pub fn operation_count(&self) -> usize {
+        match self {
+            Expression::Constant(_) | Expression::Variable(_) => 0,
+            Expression::Not(operand) => 1 + operand.operation_count(),
+            Expression::And(operands) | Expression::Or(operands) => {
+                1 + operands
+                    .iter()
+                    .map(Expression::operation_count)
+                    .sum::<usize>()
+            }
+        }
+    }
+
+    /// Finds a small sum of products with the same truth table, see [`Expression::from_minterms`]
+    pub fn minimise(&self) -> Result<Self, ExpressionError> {
+        let variables = self.variables();
+        if variables.len() > Self::MAX_VARIABLES {
+            return Err(ExpressionError::TooManyVariables(variables.len()));
+        }
+        let minterms = (0..1usize << variables.len())
+            .filter(|&minterm| {
+                self.evaluate(&|name| {
+                    let i = variables.iter().position(|other| other == name).unwrap();
+                    minterm & (1 << (variables.len() - 1 - i)) != 0
+                })
+            })
+            .collect::<Vec<_>>();
+        Self::from_minterms(variables, &minterms)
+    }
+
+    /// Builds a sum of products that is true for exactly `minterms`, where bit `i` of a minterm counting
+    /// from the highest is the state of `variables[i]`.
+    /// Every product is a prime implicant and the essential ones are always used, but the rest are picked greedily,
+    /// so there can be a sum with fewer products
+    pub fn from_minterms(
+        variables: Vec<String>,
+        minterms: &[usize],
+    ) -> Result<Self, ExpressionError> {
+        if variables.len() > Self::MAX_VARIABLES {
+            return Err(ExpressionError::TooManyVariables(variables.len()));
+        }
+        if minterms.is_empty() {
+            return Ok(Expression::Constant(false));
+        }
+        if minterms.len() == 1 << variables.len() {
+            return Ok(Expression::Constant(true));
+        }
+
+        // Terms with the first variables set come first
+        let mut products = cover(&prime_implicants(minterms), minterms)
+            .into_iter()
+            .rev()
+            .map(|Implicant { value, mask }| {
+                let mut literals = variables
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, name)| {
+                        let bit = 1 << (variables.len() - 1 - i);
+                        if mask & bit != 0 {
+                            None
+                        } else if value & bit != 0 {
+                            Some(Expression::Variable(name.clone()))
+                        } else {
+                            Some(Expression::Not(Box::new(Expression::Variable(
+                                name.clone(),
+                            ))))
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                if literals.len() == 1 {
+                    literals.pop().unwrap()
+                } else {
+                    Expression::And(literals)
+                }
+            })
+            .collect::<Vec<_>>();
+        Ok(if products.len() == 1 {
+            products.pop().unwrap()
+        } else {
+            Expression::Or(products)
+        })
+    }
+}
+
+// A product term, where bits set in `mask` are variables that do not appear in it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Implicant {
+    value: usize,
+    mask: usize,
+}
+
+impl Implicant {
+    fn covers(&self, minterm: usize) -> bool {
+        minterm & !self.mask == self.value
+    }
+}
+
+// Implicants can only combine when their values differ in one bit, so they are grouped by how many bits are set
+// and only neighbouring groups are compared
+fn prime_implicants(minterms: &[usize]) -> Vec<Implicant> {
+    let group_count = usize::BITS as usize + 1;
+    let mut primes = BTreeSet::new();
+    let mut groups = vec![BTreeSet::new(); group_count];
+    for &value in minterms {
+        groups[value.count_ones() as usize].insert(Implicant { value, mask: 0 });
+    }
+    while groups.iter().any(|group| !group.is_empty()) {
+        let mut combined = HashSet::new();
+        let mut next = vec![BTreeSet::new(); group_count];
+        for (ones, pair) in groups.windows(2).enumerate() {
+            for a in &pair[0] {
+                for b in &pair[1] {
+                    let difference = a.value ^ b.value;
+                    if a.mask == b.mask && difference.count_ones() == 1 {
+                        next[ones].insert(Implicant {
+                            value: a.value,
+                            mask: a.mask | difference,
+                        });
+                        combined.insert(*a);
+                        combined.insert(*b);
+                    }
+                }
+            }
+        }
+        primes.extend(
+            groups
+                .into_iter()
+                .flatten()
+                .filter(|implicant| !combined.contains(implicant)),
+        );
+        groups = next;
+    }
+    primes.into_iter().collect()
+}
+
+// Picks the essential prime implicants, then greedily whichever covers the most remaining minterms
+fn cover(primes: &[Implicant], minterms: &[usize]) -> Vec<Implicant> {
+    let mut chosen = BTreeSet::new();
+    for &minterm in minterms {
+        let mut covering = primes.iter().filter(|prime| prime.covers(minterm));
+        if let (Some(&prime), None) = (covering.next(), covering.next()) {
+            chosen.insert(prime);
+        }
+    }
+    let mut remaining = minterms
+        .iter()
+        .copied()
+        .filter(|&minterm| !chosen.iter().any(|prime| prime.covers(minterm)))
+        .collect::<Vec<_>>();
+    while !remaining.is_empty() {
+        let &best = primes
+            .iter()
+            .max_by_key(|prime| {
+                (
+                    remaining
+                        .iter()
+                        .filter(|&&minterm| prime.covers(minterm))
+                        .count(),
+                    prime.mask.count_ones(),
+                )
+            })
+            .unwrap();
+        remaining.retain(|&minterm| !best.covers(minterm));
+        chosen.insert(best);
+    }
+    chosen.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Component, Input, Output};
+
+    fn variables(names: &str) -> Vec<String> {
+        names.chars().map(|name| name.to_string()).collect()
+    }
+
+    fn minimise(expression: &str) -> String {
+        expression
+            .parse::<Expression>()
+            .unwrap()
+            .minimise()
+            .unwrap()
+            .to_string()
+    }
+
+    // Every implicant that only covers minterms and cannot be made any bigger
+    fn brute_force_primes(variable_count: usize, minterms: &[usize]) -> BTreeSet<Implicant> {
+        let all = (1 << variable_count) - 1;
+        let is_implicant = |implicant: &Implicant| {
+            (0..=all)
+                .filter(|&minterm| implicant.covers(minterm))
+                .all(|minterm| minterms.contains(&minterm))
+        };
+        (0..=all)
+            .flat_map(|mask| {
+                (0..=all)
+                    .filter(move |value| value & mask == 0)
+                    .map(move |value| Implicant { value, mask })
+            })
+            .filter(|implicant| {
+                is_implicant(implicant)
+                    && (0..variable_count).all(|bit| {
+                        let bit = 1 << bit;
+                        implicant.mask & bit != 0
+                            || !is_implicant(&Implicant {
+                                value: implicant.value & !bit,
+                                mask: implicant.mask | bit,
+                            })
+                    })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn prime_implicants_match_brute_force() {
+        let mut random = 0x2545F4914F6CDD1Du64;
+        for _ in 0..200 {
+            random ^= random << 13;
+            random ^= random >> 7;
+            random ^= random << 17;
+            let minterms = (0..16)
+                .filter(|minterm| random & (1 << minterm) != 0)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                prime_implicants(&minterms)
+                    .into_iter()
+                    .collect::<BTreeSet<_>>(),
+                brute_force_primes(4, &minterms),
+                "Minterms {minterms:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn minimises() {
+        assert_eq!(minimise("a & b | a & !b"), "a");
+        assert_eq!(minimise("a | !a & b"), "a | b");
+        assert_eq!(minimise("(a | b) & (a | c)"), "a | (b & c)");
+        assert_eq!(
+            minimise("a & b | a & c | b & c"),
+            "(a & b) | (a & c) | (b & c)"
+        );
+        assert_eq!(minimise("a & !b | !a & b"), "(a & !b) | (!a & b)");
+        assert_eq!(minimise("a | !a"), "1");
+        assert_eq!(minimise("a & !a"), "0");
+    }
+
+    #[test]
+    fn minimises_from_minterms() {
+        let expression =
+            Expression::from_minterms(variables("abcd"), &[0, 1, 2, 5, 6, 7, 8, 9, 10, 14])
+                .unwrap();
+        assert_eq!(
+            expression.to_string(),
+            "(!a & b & d) | (c & !d) | (!b & !c)"
+        );
+
+        let too_many = (0..=Expression::MAX_VARIABLES).map(|i| format!("x{i}"));
+        assert_eq!(
+            Expression::from_minterms(too_many.collect(), &[0]),
+            Err(ExpressionError::TooManyVariables(
+                Expression::MAX_VARIABLES + 1
+            ))
+        );
+    }
+
+    #[test]
+    fn minimising_keeps_the_truth_table() {
+        for expression in [
+            "a & (b | !c) | !(d & a) & c",
+            "!(a | b) | (c & d & !a) | (b & !d)",
+            "(a | b | c) & (!a | !b | !c) & d",
+        ] {
+            let expression = expression.parse::<Expression>().unwrap();
+            let minimised = expression.minimise().unwrap();
+            for minterm in 0..16 {
+                let state = |name: &str| minterm & (1 << "abcd".find(name).unwrap()) != 0;
+                assert_eq!(
+                    expression.evaluate(&state),
+                    minimised.evaluate(&state),
+                    "{expression} and {minimised} differ for {minterm:04b}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn extracts_long_chains() {
+        const LENGTH: usize = 20_000;
+
+        let mut scene = Scene::new();
+        let ids = (0..LENGTH)
+            .map(|_| {
+                scene.add_component(Component {
+                    typ: ComponentType::Not {
+                        input: Input { state: false },
+                        output: None,
+                    },
+                    position: Default::default(),
+                    labels: Default::default(),
+                })
+            })
+            .collect::<Vec<_>>();
+        for pair in ids.windows(2) {
+            scene.get_component_mut(pair[0]).get_outputs_mut()[0] = Some(Output {
+                component: pair[1],
+                index: 0,
+            });
+        }
+
+        let extracted = Expression::extract(&scene, Pin::Output(ids[LENGTH - 1], 0));
+        let mut expression = &extracted;
+        let mut depth = 0;
+        while let Expression::Not(operand) = expression {
+            expression = operand;
+            depth += 1;
+        }
+        assert_eq!(depth, LENGTH);
+        assert!(matches!(expression, Expression::Variable(_)));
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+
+use crate::{Scene, Snapshot, Update};
+
+struct Delta {
+    flipped: Vec<(usize, u64)>,
+    changed_before: Vec<usize>,
+    changed_after: Vec<usize>,
+}
+
+/// Records what changed in a [`Scene`] on each tick so it can be stepped backwards and forwards.
+/// Only the most recent `capacity` ticks are kept
+pub struct History {
+    capacity: usize,
+    deltas: VecDeque<Delta>,
+    oldest_tick: usize,
+    tick: usize,
+    current: Snapshot,
+}
+
+impl History {
+    pub fn new(scene: &Scene, capacity: usize) -> Self {
+        Self {
+            capacity,
+            deltas: VecDeque::new(),
+            oldest_tick: scene.tick(),
+            tick: scene.tick(),
+            current: scene.snapshot(),
+        }
+    }
+
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+
+    pub fn oldest_tick(&self) -> usize {
+        self.oldest_tick
+    }
+
+    pub fn latest_tick(&self) -> usize {
+        self.oldest_tick + self.deltas.len()
+    }
+
+    pub fn is_at_latest(&self) -> bool {
+        self.tick == self.latest_tick()
+    }
+
+    /// Forgets every recorded tick, starting again from the current state and tick of `scene`.
+    /// This must be called whenever components are added, since older snapshots no longer fit the scene
+    pub fn clear(&mut self, scene: &Scene) {
+        self.deltas.clear();
+        self.tick = scene.tick();
+        self.oldest_tick = self.tick;
+        self.current = scene.snapshot();
+    }
+
+    /// Updates `scene` and records the tick, throwing away any ticks after the current one
+    pub fn update(&mut self, scene: &mut Scene) -> Update {
+        let update = scene.update();
+        self.record(scene);
+        update
+    }
+
+    /// Records the state of `scene` as the tick after the current one, throwing away any ticks after the current one
+    pub fn record(&mut self, scene: &Scene) {
+        self.deltas.truncate(self.tick - self.oldest_tick);
+        let snapshot = scene.snapshot();
+        self.deltas.push_back(Delta {
+            flipped: self.current.difference(&snapshot),
+            changed_before: std::mem::take(&mut self.current.changed),
+            changed_after: snapshot.changed.clone(),
+        });
+        self.current = snapshot;
+        self.tick += 1;
+        if self.deltas.len() > self.capacity {
+            self.deltas.pop_front();
+            self.oldest_tick += 1;
+        }
+    }
+
+    pub fn step_back(&mut self, scene: &mut Scene) -> bool {
+        let moved = self.back();
+        if moved {
+            scene.restore(&self.current);
+            scene.set_tick(self.tick);
+        }
+        moved
+    }
+
+    pub fn step_forward(&mut self, scene: &mut Scene) -> bool {
+        let moved = self.forward();
+        if moved {
+            scene.restore(&self.current);
+            scene.set_tick(self.tick);
+        }
+        moved
+    }
+
+    pub fn jump_to(&mut self, scene: &mut Scene, tick: usize) {
+        let tick = tick.clamp(self.oldest_tick, self.latest_tick());
+        while self.tick > tick {
+            self.back();
+        }
+        while self.tick < tick {
+            self.forward();
+        }
+        scene.restore(&self.current);
+        scene.set_tick(self.tick);
+    }
+
+    fn back(&mut self) -> bool {
+        if self.tick == self.oldest_tick {
+            return false;
+        }
+        self.tick -= 1;
+        let delta = &self.deltas[self.tick - self.oldest_tick];
+        self.current.flip(&delta.flipped);
+        self.current.changed = delta.changed_before.clone();
+        true
+    }
+
+    fn forward(&mut self) -> bool {
+        if self.is_at_latest() {
+            return false;
+        }
+        let delta = &self.deltas[self.tick - self.oldest_tick];
+        self.current.flip(&delta.flipped);
+        self.current.changed = delta.changed_after.clone();
+        self.tick += 1;
+        true
+    }
+}
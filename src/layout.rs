@@ -0,0 +1,184 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use raylib::math::Vector2;
+
+use crate::{ComponentID, Scene};
+
+const COLUMN_SPACING: f32 = 250.0;
+// The gap left between components in the same column
+const ROW_GAP: f32 = 50.0;
+
+/// Places `components` as a tree centred on `position`, with each component to the left of the one its output drives.
+/// Connections to components outside of `components` are ignored
+pub fn auto_layout(scene: &mut Scene, components: &[ComponentID], position: Vector2) {
+    let members = components.iter().copied().collect::<HashSet<_>>();
+    let mut children = HashMap::<ComponentID, Vec<(usize, ComponentID)>>::new();
+    let mut has_parent = HashSet::new();
+    for &id in components {
+        if let Some(output) = scene
+            .get_component(id)
+            .get_outputs()
+            .iter()
+            .flatten()
+            .find(|output| members.contains(&output.component))
+        {
+            children
+                .entry(output.component)
+                .or_default()
+                .push((output.index, id));
+            has_parent.insert(id);
+        }
+    }
+    for children in children.values_mut() {
+        children.sort();
+    }
+
+    let mut positions = HashMap::new();
+    let mut next_y = 0.0;
+    for &id in components.iter().filter(|id| !has_parent.contains(id)) {
+        place(scene, id, &children, &mut positions, &mut next_y);
+    }
+    for &id in components {
+        if !positions.contains_key(&id) {
+            place(scene, id, &children, &mut positions, &mut next_y);
+        }
+    }
+
+    let (min, max) = positions.values().fold(
+        (
+            Vector2::new(f32::INFINITY, f32::INFINITY),
+            Vector2::new(f32::NEG_INFINITY, f32::NEG_INFINITY),
+        ),
+        |(min, max), p| {
+            (
+                Vector2::new(min.x.min(p.x), min.y.min(p.y)),
+                Vector2::new(max.x.max(p.x), max.y.max(p.y)),
+            )
+        },
+    );
+    let offset = position - (min + max) * 0.5;
+    for (id, p) in positions {
+        scene.get_component_mut(id).position = p + offset;
+    }
+}
+
+struct Frame {
+    id: ComponentID,
+    depth: usize,
+    top: f32,
+    next_child: usize,
+    child_ys: Vec<f32>,
+}
+
+// Places the tree under `root` below `next_y`, with each leaf below the last and each parent level with the middle of
+// its children. Every component stays between the top and bottom of its own subtree, so components in the same column
+// never overlap however tall they are
+fn place(
+    scene: &Scene,
+    root: ComponentID,
+    children: &HashMap<ComponentID, Vec<(usize, ComponentID)>>,
+    positions: &mut HashMap<ComponentID, Vector2>,
+    next_y: &mut f32,
+) {
+    // Components that drive each other through delays can form a loop, so they are claimed before being placed
+    positions.insert(root, Vector2::zero());
+    let mut stack = vec![Frame {
+        id: root,
+        depth: 0,
+        top: *next_y,
+        next_child: 0,
+        child_ys: vec![],
+    }];
+    while let Some(frame) = stack.last_mut() {
+        if let Some(&(_, child)) = children
+            .get(&frame.id)
+            .and_then(|children| children.get(frame.next_child))
+        {
+            frame.next_child += 1;
+            if let Entry::Vacant(entry) = positions.entry(child) {
+                entry.insert(Vector2::zero());
+                let depth = frame.depth + 1;
+                stack.push(Frame {
+                    id: child,
+                    depth,
+                    top: *next_y,
+                    next_child: 0,
+                    child_ys: vec![],
+                });
+            }
+            continue;
+        }
+
+        let frame = stack.pop().unwrap();
+        let half_height = scene.get_component(frame.id).get_size().y * 0.5;
+        let middle = if frame.child_ys.is_empty() {
+            f32::NEG_INFINITY
+        } else {
+            frame.child_ys.iter().sum::<f32>() / frame.child_ys.len() as f32
+        };
+        let y = middle.max(frame.top + half_height);
+        *next_y = next_y.max(y + half_height + ROW_GAP);
+        positions.insert(
+            frame.id,
+            Vector2 {
+                x: -(frame.depth as f32) * COLUMN_SPACING,
+                y,
+            },
+        );
+        if let Some(parent) = stack.last_mut() {
+            parent.child_ys.push(y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CircuitBuilder, Diagnostic};
+
+    fn overlapping(scene: &Scene) -> Vec<Diagnostic> {
+        scene
+            .lint()
+            .into_iter()
+            .filter(|diagnostic| matches!(diagnostic, Diagnostic::Overlapping(..)))
+            .collect()
+    }
+
+    #[test]
+    fn spaces_tall_components() {
+        let mut b = CircuitBuilder::new();
+        let nots = (0..8)
+            .map(|i| {
+                let bit = b.input_port(&format!("a{i}"), 4).remove(0);
+                b.not(bit)
+            })
+            .collect::<Vec<_>>();
+        b.output_port("y", nots);
+        let circuit = b.build();
+        assert_eq!(overlapping(&circuit.scene), []);
+    }
+
+    #[test]
+    fn lays_out_loops() {
+        let mut b = CircuitBuilder::new();
+        let x = b.input("x");
+        let (q, feedback) = b.feedback(false);
+        let or = b.or(x, q);
+        let not = b.not(or);
+        b.close(feedback, not);
+        let circuit = b.build();
+        assert_eq!(overlapping(&circuit.scene), []);
+    }
+
+    #[test]
+    fn lays_out_deep_chains() {
+        let mut b = CircuitBuilder::new();
+        let mut wire = b.input("x");
+        for _ in 0..100_000 {
+            wire = b.not(wire);
+        }
+        b.output("y", wire);
+        let circuit = b.build();
+        assert_eq!(overlapping(&circuit.scene), []);
+    }
+}
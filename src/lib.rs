@@ -1,5 +1,38 @@
+mod blif;
+mod builder;
+mod compiled;
 mod components;
+mod dot;
+mod equivalence;
+mod expression;
+mod history;
+mod layout;
+mod lint;
+mod repl;
+mod sat;
 mod scene;
+mod scripting;
+mod synthesis;
+mod test_vectors;
+mod trace;
+mod truth_table;
+mod verilog;
 
+pub use blif::*;
+pub use builder::*;
+pub use compiled::*;
 pub use components::*;
+pub use equivalence::*;
+pub use expression::*;
+pub use history::*;
+pub use layout::*;
+pub use lint::*;
+pub use repl::*;
 pub use scene::*;
+pub use scripting::*;
+pub use synthesis::*;
+pub use test_vectors::*;
+pub use trace::*;
+pub use truth_table::*;
+
+pub use logic_sim_macros::circuit;
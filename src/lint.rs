@@ -0,0 +1,536 @@
+use std::collections::{HashMap, HashSet};
+
+use derive_more::Display;
+
+use crate::{ComponentID, ComponentType, Pin, PortDirection, Scene};
+
+/// Something [`Scene::lint`] found that is probably a mistake, even though the scene can still be simulated
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+pub enum Diagnostic {
+    #[display(fmt = "{_0} has nothing connected to it")]
+    FloatingInput(Pin),
+    #[display(fmt = "{_0} is not connected to anything")]
+    UnconnectedOutput(Pin),
+    #[display(fmt = "Component {_0} does not affect any output port or assert")]
+    Unused(ComponentID),
+    #[display(fmt = "Component {component} always outputs {}", "*state as u8")]
+    ConstantOutput { component: ComponentID, state: bool },
+    #[display(fmt = "{input} is driven by {}", "format_pins(drivers)")]
+    MultipleDrivers { input: Pin, drivers: Vec<Pin> },
+    #[display(fmt = "Components {_0} and {_1} overlap")]
+    Overlapping(ComponentID, ComponentID),
+    #[display(fmt = "There is more than one port called {name}")]
+    DuplicatePort {
+        name: String,
+        components: Vec<ComponentID>,
+    },
+}
+
+impl Diagnostic {
+    /// The components the diagnostic is about, which the editor marks
+    pub fn get_components(&self) -> Vec<ComponentID> {
+        match self {
+            Diagnostic::FloatingInput(Pin::Input(id, _) | Pin::Output(id, _))
+            | Diagnostic::UnconnectedOutput(Pin::Input(id, _) | Pin::Output(id, _))
+            | Diagnostic::Unused(id)
+            | Diagnostic::ConstantOutput { component: id, .. }
+            | Diagnostic::MultipleDrivers {
+                input: Pin::Input(id, _) | Pin::Output(id, _),
+                ..
+            } => vec![*id],
+            Diagnostic::Overlapping(first, second) => vec![*first, *second],
+            Diagnostic::DuplicatePort { components, .. } => components.clone(),
+        }
+    }
+}
+
+impl Scene {
+    /// Looks for likely mistakes without simulating anything: inputs with nothing connected, outputs that are not
+    /// connected, components that cannot affect an output port or an assert, gates that always output the same state,
+    /// inputs driven by more than one output, components drawn on top of each other, and ports that share a name.
+    ///
+    /// A tunnel's input only counts as floating when no tunnel with its name is driven, and its output only needs
+    /// connecting when its input is not driven. Unused components are only looked for when the scene has output ports
+    pub fn lint(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+
+        let mut drivers = HashMap::<Pin, Vec<Pin>>::new();
+        for id in self {
+            for (index, output) in self.get_component(id).get_outputs().iter().enumerate() {
+                if let Some(output) = output {
+                    drivers
+                        .entry(Pin::Input(output.component, output.index))
+                        .or_default()
+                        .push(Pin::Output(id, index));
+                }
+            }
+        }
+        let is_driven =
+            |id: ComponentID, index: usize| drivers.contains_key(&Pin::Input(id, index));
+        let mut tunnels = HashMap::<&str, Vec<ComponentID>>::new();
+        for id in self {
+            if let ComponentType::Tunnel { name, .. } = &self.get_component(id).typ {
+                tunnels.entry(name).or_default().push(id);
+            }
+        }
+
+        for id in self {
+            let component = self.get_component(id);
+            for index in 0..component.get_inputs().len() {
+                let input = Pin::Input(id, index);
+                let floating = match &component.typ {
+                    ComponentType::Tunnel { name, .. } => !tunnels[name.as_str()]
+                        .iter()
+                        .any(|&tunnel| is_driven(tunnel, 0)),
+                    _ => !is_driven(id, index),
+                };
+                if floating {
+                    diagnostics.push(Diagnostic::FloatingInput(input));
+                }
+                if let Some(drivers) = drivers.get(&input).filter(|drivers| drivers.len() > 1) {
+                    diagnostics.push(Diagnostic::MultipleDrivers {
+                        input,
+                        drivers: drivers.clone(),
+                    });
+                }
+            }
+            for (index, output) in component.get_outputs().iter().enumerate() {
+                let sends_through_tunnel = component.typ.is_tunnel() && is_driven(id, 0);
+                if output.is_none() && !sends_through_tunnel {
+                    diagnostics.push(Diagnostic::UnconnectedOutput(Pin::Output(id, index)));
+                }
+            }
+        }
+
+        let ports = self.ports();
+        if ports
+            .iter()
+            .any(|port| port.direction == PortDirection::Output)
+        {
+            let used = self.find_used(&drivers, &tunnels);
+            for id in self {
+                let typ = &self.get_component(id).typ;
+                if !used.contains(&id) && !typ.is_output_port() && !typ.is_assert() {
+                    diagnostics.push(Diagnostic::Unused(id));
+                }
+            }
+        }
+
+        let constants = self.find_constants(&drivers, &tunnels, !ports.is_empty());
+        for id in self {
+            let typ = &self.get_component(id).typ;
+            if !typ.is_not() && !typ.is_or() {
+                continue;
+            }
+            if let Some(&state) = constants.get(&id) {
+                diagnostics.push(Diagnostic::ConstantOutput {
+                    component: id,
+                    state,
+                });
+            }
+        }
+
+        // Sorted by their left edges, components only need checking against those that start before their right edge
+        let mut bounds = self
+            .into_iter()
+            .map(|id| {
+                let component = self.get_component(id);
+                let size = component.get_size();
+                (
+                    id,
+                    component.position - size * 0.5,
+                    component.position + size * 0.5,
+                )
+            })
+            .collect::<Vec<_>>();
+        bounds.sort_by(|(_, a, _), (_, b, _)| a.x.total_cmp(&b.x));
+        for (i, &(id, min, max)) in bounds.iter().enumerate() {
+            for &(other, other_min, other_max) in bounds[i + 1..]
+                .iter()
+                .take_while(|(_, other_min, _)| other_min.x < max.x)
+            {
+                if other_min.y < max.y && min.y < other_max.y {
+                    diagnostics.push(Diagnostic::Overlapping(id.min(other), id.max(other)));
+                }
+            }
+        }
+
+        let mut names = Vec::<(String, Vec<ComponentID>)>::new();
+        for port in ports {
+            match names.iter_mut().find(|(name, _)| *name == port.name) {
+                Some((_, components)) => components.push(port.component),
+                None => names.push((port.name, vec![port.component])),
+            }
+        }
+        for (name, components) in names {
+            if components.len() > 1 {
+                diagnostics.push(Diagnostic::DuplicatePort { name, components });
+            }
+        }
+
+        diagnostics
+    }
+
+    // Every component that an output port or an assert reads from, walking back through the drivers
+    fn find_used(
+        &self,
+        drivers: &HashMap<Pin, Vec<Pin>>,
+        tunnels: &HashMap<&str, Vec<ComponentID>>,
+    ) -> HashSet<ComponentID> {
+        let mut used = HashSet::new();
+        let mut stack = self
+            .into_iter()
+            .filter(|&id| {
+                let typ = &self.get_component(id).typ;
+                typ.is_output_port() || typ.is_assert()
+            })
+            .collect::<Vec<_>>();
+        while let Some(id) = stack.pop() {
+            if !used.insert(id) {
+                continue;
+            }
+            let component = self.get_component(id);
+            // A tunnel outputs what every tunnel with its name is given
+            if let ComponentType::Tunnel { name, .. } = &component.typ {
+                stack.extend(&tunnels[name.as_str()]);
+            }
+            for index in 0..component.get_inputs().len() {
+                for driver in drivers.get(&Pin::Input(id, index)).into_iter().flatten() {
+                    if let Pin::Output(driver, _) = driver {
+                        stack.push(*driver);
+                    }
+                }
+            }
+        }
+        used
+    }
+
+    // The state of every gate and tunnel whose output does not depend on anything that can change.
+    // Delays and input ports can always change, and so can inputs with nothing connected, unless the scene has
+    // ports to take its inputs from, in which case they are stuck in their current state
+    fn find_constants(
+        &self,
+        drivers: &HashMap<Pin, Vec<Pin>>,
+        tunnels: &HashMap<&str, Vec<ComponentID>>,
+        has_ports: bool,
+    ) -> HashMap<ComponentID, bool> {
+        // Tunnels sharing a name all output the same state, so they are handled together as their first tunnel
+        let tunnel_group = |id: ComponentID| match &self.get_component(id).typ {
+            ComponentType::Tunnel { name, .. } => Some(&tunnels[name.as_str()][..]),
+            _ => None,
+        };
+
+        // Each gate or group counts its inputs that are not yet known to be stuck off, and every input that becomes
+        // stuck is passed on to what reads it, so each component is only looked at when one of its inputs is decided
+        let mut unknown = HashMap::<ComponentID, usize>::new();
+        let mut readers = HashMap::<ComponentID, Vec<ComponentID>>::new();
+        let mut stuck = vec![];
+        for id in self {
+            let component = self.get_component(id);
+            if !component.typ.is_not() && !component.typ.is_or() && !component.typ.is_tunnel() {
+                continue;
+            }
+            let node = tunnel_group(id).map_or(id, |group| group[0]);
+            for (index, input) in component.get_inputs().iter().enumerate() {
+                *unknown.entry(node).or_default() += 1;
+                match drivers
+                    .get(&Pin::Input(id, index))
+                    .and_then(|drivers| drivers.first())
+                {
+                    Some(Pin::Output(driver, _)) => readers.entry(*driver).or_default().push(node),
+                    Some(Pin::Input(..)) => unreachable!(),
+                    None if has_ports => stuck.push((node, input.state)),
+                    None => {}
+                }
+            }
+        }
+
+        let mut constants = HashMap::new();
+        while let Some((node, input)) = stuck.pop() {
+            if constants.contains_key(&node) {
+                continue;
+            }
+            // An `Or` is on if any input is stuck on, and off only if every input is stuck off
+            let state = match &self.get_component(node).typ {
+                ComponentType::Not { .. } => Some(!input),
+                _ if input => Some(true),
+                _ => {
+                    let unknown = unknown.get_mut(&node).unwrap();
+                    *unknown -= 1;
+                    (*unknown == 0).then_some(false)
+                }
+            };
+            if let Some(state) = state {
+                let group = tunnel_group(node).unwrap_or(std::slice::from_ref(&node));
+                for &id in group {
+                    constants.insert(id, state);
+                    for &reader in readers.get(&id).into_iter().flatten() {
+                        stuck.push((reader, state));
+                    }
+                }
+            }
+        }
+        constants
+    }
+}
+
+fn format_pins(pins: &[Pin]) -> String {
+    pins.iter()
+        .map(|pin| pin.to_string())
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+#[cfg(test)]
+mod tests {
+    use raylib::math::Vector2;
+
+    use super::*;
+    use crate::{
+        synthesis::{not_gate, or_gate},
+        AssertCondition, Component, Input, Output,
+    };
+
+    // Adds components in a row, far enough apart that they do not overlap
+    fn add(scene: &mut Scene, typ: ComponentType) -> ComponentID {
+        let x = scene.into_iter().count() as f32 * 200.0;
+        scene.add_component(Component {
+            typ,
+            position: Vector2 { x, y: 0.0 },
+            labels: Default::default(),
+        })
+    }
+
+    fn wire(scene: &mut Scene, from: ComponentID, to: ComponentID, index: usize) {
+        scene
+            .connect(Pin::Output(from, 0), Pin::Input(to, index))
+            .unwrap();
+    }
+
+    fn input_port(name: &str) -> ComponentType {
+        ComponentType::InputPort {
+            name: name.to_string(),
+            outputs: vec![None],
+            value: 0,
+        }
+    }
+
+    fn output_port(name: &str) -> ComponentType {
+        ComponentType::OutputPort {
+            name: name.to_string(),
+            inputs: vec![Input { state: false }],
+        }
+    }
+
+    fn tunnel(name: &str) -> ComponentType {
+        ComponentType::Tunnel {
+            name: name.to_string(),
+            input: Input { state: false },
+            output: None,
+        }
+    }
+
+    fn constants(scene: &Scene) -> Vec<Diagnostic> {
+        scene
+            .lint()
+            .into_iter()
+            .filter(|diagnostic| matches!(diagnostic, Diagnostic::ConstantOutput { .. }))
+            .collect()
+    }
+
+    #[test]
+    fn passes_clean_circuits() {
+        let mut scene = Scene::new();
+        let a = add(&mut scene, input_port("a"));
+        let b = add(&mut scene, input_port("b"));
+        let or = add(&mut scene, or_gate());
+        let not = add(&mut scene, not_gate());
+        let y = add(&mut scene, output_port("y"));
+        wire(&mut scene, a, or, 0);
+        wire(&mut scene, b, or, 1);
+        wire(&mut scene, or, not, 0);
+        wire(&mut scene, not, y, 0);
+        assert_eq!(scene.lint(), []);
+    }
+
+    #[test]
+    fn finds_floating_inputs_and_unconnected_outputs() {
+        let mut scene = Scene::new();
+        let not = add(&mut scene, not_gate());
+        // Only one tunnel with a name needs its input driven, and only the others need their outputs connected
+        let sender = add(&mut scene, tunnel("x"));
+        let receiver = add(&mut scene, tunnel("x"));
+        let other = add(&mut scene, not_gate());
+        wire(&mut scene, not, sender, 0);
+        wire(&mut scene, receiver, other, 0);
+        assert_eq!(
+            scene.lint(),
+            [
+                Diagnostic::FloatingInput(Pin::Input(not, 0)),
+                Diagnostic::UnconnectedOutput(Pin::Output(other, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_unused_components() {
+        let mut scene = Scene::new();
+        let a = add(&mut scene, input_port("a"));
+        let y = add(&mut scene, output_port("y"));
+        let b = add(&mut scene, input_port("b"));
+        let asserted = add(&mut scene, not_gate());
+        let assert = add(
+            &mut scene,
+            ComponentType::Assert {
+                inputs: vec![Input { state: false }],
+                condition: AssertCondition::AlwaysHigh,
+            },
+        );
+        let c = add(&mut scene, input_port("c"));
+        let unused = add(&mut scene, not_gate());
+        wire(&mut scene, a, y, 0);
+        wire(&mut scene, b, asserted, 0);
+        wire(&mut scene, asserted, assert, 0);
+        wire(&mut scene, c, unused, 0);
+        assert_eq!(
+            scene.lint(),
+            [
+                Diagnostic::UnconnectedOutput(Pin::Output(unused, 0)),
+                Diagnostic::Unused(c),
+                Diagnostic::Unused(unused),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_constant_outputs() {
+        // The readers are added first, so states have to be passed back to them
+        let mut scene = Scene::new();
+        let y = add(&mut scene, output_port("y"));
+        let reader = add(&mut scene, or_gate());
+        let receiver = add(&mut scene, tunnel("t"));
+        let sender = add(&mut scene, tunnel("t"));
+        let not = add(&mut scene, not_gate());
+        let off = add(&mut scene, or_gate());
+        let a = add(&mut scene, input_port("a"));
+        let b = add(&mut scene, input_port("b"));
+        let changing = add(&mut scene, not_gate());
+        let z = add(&mut scene, output_port("z"));
+        wire(&mut scene, reader, y, 0);
+        wire(&mut scene, receiver, reader, 0);
+        wire(&mut scene, not, sender, 0);
+        wire(&mut scene, off, not, 0);
+        wire(&mut scene, a, reader, 1);
+        wire(&mut scene, b, changing, 0);
+        wire(&mut scene, changing, z, 0);
+        assert_eq!(
+            constants(&scene),
+            [
+                Diagnostic::ConstantOutput {
+                    component: reader,
+                    state: true,
+                },
+                Diagnostic::ConstantOutput {
+                    component: not,
+                    state: true,
+                },
+                Diagnostic::ConstantOutput {
+                    component: off,
+                    state: false,
+                },
+            ]
+        );
+
+        // Without ports, inputs with nothing connected could be set to anything
+        let mut scene = Scene::new();
+        let off = add(&mut scene, or_gate());
+        let not = add(&mut scene, not_gate());
+        wire(&mut scene, off, not, 0);
+        assert_eq!(constants(&scene), []);
+    }
+
+    #[test]
+    fn finds_constants_along_long_chains() {
+        // Connected directly, since checking each connection for loops would take quadratic time
+        let mut scene = Scene::new();
+        let mut x = 0.0;
+        let mut chain = |scene: &mut Scene, typ: ComponentType, last: Option<ComponentID>| {
+            x += 200.0;
+            let id = scene.add_component(Component {
+                typ,
+                position: Vector2 { x, y: 0.0 },
+                labels: Default::default(),
+            });
+            if let Some(last) = last {
+                scene.get_component_mut(last).get_outputs_mut()[0] = Some(Output {
+                    component: id,
+                    index: 0,
+                });
+            }
+            id
+        };
+        let mut last = chain(&mut scene, not_gate(), None);
+        for i in 0..10_000 {
+            chain(&mut scene, tunnel(&i.to_string()), Some(last));
+            let receiver = chain(&mut scene, tunnel(&i.to_string()), None);
+            last = chain(&mut scene, not_gate(), Some(receiver));
+        }
+        chain(&mut scene, output_port("y"), Some(last));
+        assert_eq!(constants(&scene).len(), 10_001);
+    }
+
+    #[test]
+    fn finds_multiple_drivers() {
+        let mut scene = Scene::new();
+        let first = add(&mut scene, not_gate());
+        let second = add(&mut scene, not_gate());
+        let or = add(&mut scene, or_gate());
+        for id in [first, second] {
+            scene.get_component_mut(id).get_outputs_mut()[0] = Some(Output {
+                component: or,
+                index: 0,
+            });
+        }
+        assert!(scene.lint().contains(&Diagnostic::MultipleDrivers {
+            input: Pin::Input(or, 0),
+            drivers: vec![Pin::Output(first, 0), Pin::Output(second, 0)],
+        }));
+    }
+
+    #[test]
+    fn finds_overlapping_components() {
+        let mut scene = Scene::new();
+        let first = add(&mut scene, not_gate());
+        add(&mut scene, not_gate());
+        let third = add(&mut scene, not_gate());
+        scene.get_component_mut(first).position = Vector2 { x: 500.0, y: 20.0 };
+        scene.get_component_mut(third).position = Vector2 { x: 420.0, y: 0.0 };
+        let overlapping = scene
+            .lint()
+            .into_iter()
+            .filter(|diagnostic| matches!(diagnostic, Diagnostic::Overlapping(..)))
+            .collect::<Vec<_>>();
+        assert_eq!(overlapping, [Diagnostic::Overlapping(first, third)]);
+    }
+
+    #[test]
+    fn finds_duplicate_ports() {
+        let mut scene = Scene::new();
+        let first = add(&mut scene, input_port("a"));
+        add(&mut scene, input_port("b"));
+        let second = add(&mut scene, output_port("a"));
+        wire(&mut scene, first, second, 0);
+        assert!(scene.lint().contains(&Diagnostic::DuplicatePort {
+            name: "a".to_string(),
+            components: vec![first, second],
+        }));
+        assert_eq!(
+            scene
+                .lint()
+                .iter()
+                .filter(|diagnostic| matches!(diagnostic, Diagnostic::DuplicatePort { .. }))
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use crate::{parse_port_value, ComponentID, Pin, Scene};
+
+const HELP: &str = "Commands:
+  set <pin> <0|1>             Sets an input that nothing is connected to
+  set <port> <value>          Sets an input port, to a value like 5, 0b101 or 0x5
+  step [ticks]                Updates the scene, once by default
+  show <component|pin|port>   Shows a component like dump does, or the state of a pin or port
+  ports                       Shows every port and its value
+  lint                        Shows anything in the scene that looks like a mistake
+  watch <pin>                 Shows the state of a pin after every step
+  unwatch <pin>
+  connect <output> <input>    Connects an output to an input, like connect 1.out0 4.1
+  disconnect <output>
+  label <component|pin> [text]
+                              Labels a component or a pin, or removes its label
+  dump                        Shows every component
+  help";
+
+/// Runs text commands like `set 3.0 1` and `step 10` against a scene, for exploring it without the editor.
+///
+/// Pins are written the way they are displayed or by their name. Outputs can also be written like inputs,
+/// so `connect 1.0 4.1` connects the first output of component 1 to the second input of component 4
+pub struct Repl {
+    scene: Scene,
+    watched: Vec<(String, Pin)>,
+}
+
+impl Repl {
+    pub fn new(scene: Scene) -> Self {
+        Self {
+            scene,
+            watched: vec![],
+        }
+    }
+
+    pub fn get_scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    pub fn into_scene(self) -> Scene {
+        self.scene
+    }
+
+    /// Runs one line, returning what it printed
+    pub fn execute(&mut self, line: &str) -> Result<String, String> {
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+        match tokens.as_slice() {
+            [] => Ok(String::new()),
+            ["set", name, value] if self.scene.find_port(name).is_ok() => {
+                let value =
+                    parse_port_value(value).ok_or_else(|| format!("'{value}' is not a value"))?;
+                self.scene
+                    .set_port(name, value)
+                    .map_err(|error| error.to_string())?;
+                Ok(String::new())
+            }
+            ["set", pin, state] => {
+                let state = match *state {
+                    "0" => false,
+                    "1" => true,
+                    _ => return Err(format!("'{state}' is not 0 or 1")),
+                };
+                match self.find_pin(pin)? {
+                    Pin::Input(id, index) if self.scene.get_driver(id, index).is_none() => {
+                        self.scene.get_component_mut(id).get_inputs_mut()[index].state = state;
+                        Ok(String::new())
+                    }
+                    _ => Err(format!(
+                        "{pin} is not an input that nothing is connected to"
+                    )),
+                }
+            }
+            ["step"] => Ok(self.step(1)),
+            ["step", ticks] => {
+                let ticks = ticks
+                    .parse()
+                    .map_err(|_| format!("'{ticks}' is not a number of ticks"))?;
+                Ok(self.step(ticks))
+            }
+            ["show", name] => match name.parse() {
+                Ok(id) if id < self.scene.into_iter().len() => {
+                    let id = ComponentID(id);
+                    Ok(format!("{id}: {}", self.scene.get_component(id)))
+                }
+                Ok(_) => Err(format!("There is no component {name}")),
+                Err(_) => match self.scene.get_port(name) {
+                    Ok(value) => Ok(format!("{name} = {value}")),
+                    Err(_) => {
+                        let pin = self.find_pin(name)?;
+                        Ok(format!("{name} = {}", self.scene.get_pin_state(pin) as u8))
+                    }
+                },
+            },
+            ["ports"] => Ok(self
+                .scene
+                .ports()
+                .iter()
+                .map(|port| {
+                    format!(
+                        "{} {} [{}] = {}",
+                        port.direction,
+                        port.name,
+                        port.width,
+                        self.scene.get_port(&port.name).unwrap()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")),
+            ["watch", name] => {
+                let pin = self.find_pin(name)?;
+                if !self.watched.iter().any(|&(_, other)| other == pin) {
+                    self.watched.push((name.to_string(), pin));
+                }
+                Ok(String::new())
+            }
+            ["unwatch", name] => {
+                let pin = self.find_pin(name)?;
+                self.watched.retain(|&(_, other)| other != pin);
+                Ok(String::new())
+            }
+            ["connect", output, input] => {
+                let output = self.find_output(output)?;
+                let input = self.find_pin(input)?;
+                self.scene
+                    .connect(output, input)
+                    .map_err(|error| error.to_string())?;
+                Ok(String::new())
+            }
+            ["disconnect", output] => {
+                let output = self.find_output(output)?;
+                self.scene
+                    .disconnect(output)
+                    .map_err(|error| error.to_string())?;
+                Ok(String::new())
+            }
+            ["label", name, label @ ..] => {
+                let label = (!label.is_empty()).then(|| label.join(" "));
+                match name.parse() {
+                    Ok(id) if id < self.scene.into_iter().len() => {
+                        self.scene
+                            .get_component_mut(ComponentID(id))
+                            .labels
+                            .component = label;
+                    }
+                    Ok(_) => return Err(format!("There is no component {name}")),
+                    Err(_) => {
+                        let pin = self.find_pin(name)?;
+                        self.scene.set_pin_label(pin, label);
+                    }
+                }
+                Ok(String::new())
+            }
+            ["lint"] => Ok(self
+                .scene
+                .lint()
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")),
+            ["dump"] => Ok(self.scene.to_string()),
+            ["help"] => Ok(HELP.to_string()),
+            [command, ..] => Err(format!("Unknown command '{command}', try help")),
+        }
+    }
+
+    fn step(&mut self, ticks: usize) -> String {
+        let update = self.scene.step(ticks);
+        let mut lines = update
+            .violations
+            .iter()
+            .map(|violation| violation.to_string())
+            .collect::<Vec<_>>();
+        lines.push(format!("Tick {}", self.scene.tick()));
+        for (name, pin) in &self.watched {
+            lines.push(format!(
+                "  {name} = {}",
+                self.scene.get_pin_state(*pin) as u8
+            ));
+        }
+        lines.join("\n")
+    }
+
+    fn find_pin(&self, name: &str) -> Result<Pin, String> {
+        self.scene
+            .find_pin(name)
+            .ok_or_else(|| format!("There is no pin {name}"))
+    }
+
+    fn find_output(&self, name: &str) -> Result<Pin, String> {
+        self.scene
+            .find_output(name)
+            .ok_or_else(|| format!("{name} is not an output"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CircuitBuilder;
+
+    fn repl() -> Repl {
+        let mut b = CircuitBuilder::new();
+        let [a0, a1] = b.input_port("a", 2).try_into().ok().unwrap();
+        let or = b.or(a0, a1);
+        b.output_port("y", [or]);
+        let x = b.input("x");
+        let n = b.not(x);
+        b.output("n", n);
+        Repl::new(b.build().scene)
+    }
+
+    #[test]
+    fn sets_and_shows_ports() {
+        let mut repl = repl();
+        assert_eq!(repl.execute("set a 0b11"), Ok(String::new()));
+        assert_eq!(repl.execute("step"), Ok("Tick 1".to_string()));
+        assert_eq!(repl.execute("show a"), Ok("a = 3".to_string()));
+        assert_eq!(repl.execute("show y"), Ok("y = 1".to_string()));
+        assert_eq!(
+            repl.execute("ports"),
+            Ok("Input a [2] = 3\nOutput y [1] = 1".to_string())
+        );
+        assert_eq!(
+            repl.execute("set a 4"),
+            Err("4 does not fit in the 2 bits of a".to_string())
+        );
+        assert_eq!(
+            repl.execute("set a x"),
+            Err("'x' is not a value".to_string())
+        );
+    }
+
+    #[test]
+    fn sets_and_shows_pins() {
+        let mut repl = repl();
+        assert_eq!(repl.execute("set 3.0 1"), Ok(String::new()));
+        assert_eq!(repl.execute("show 3.0"), Ok("3.0 = 1".to_string()));
+        assert_eq!(
+            repl.execute("set 3.0 2"),
+            Err("'2' is not 0 or 1".to_string())
+        );
+        assert_eq!(
+            repl.execute("set 2.0 1"),
+            Err("2.0 is not an input that nothing is connected to".to_string())
+        );
+        assert_eq!(
+            repl.execute("set 9.0 1"),
+            Err("There is no pin 9.0".to_string())
+        );
+        assert_eq!(
+            repl.execute("show nope"),
+            Err("There is no pin nope".to_string())
+        );
+    }
+
+    #[test]
+    fn shows_components() {
+        let mut repl = repl();
+        let expected = format!("1: {}", repl.get_scene().get_component(ComponentID(1)));
+        assert_eq!(repl.execute("show 1"), Ok(expected));
+        assert_eq!(
+            repl.execute("show 9"),
+            Err("There is no component 9".to_string())
+        );
+        assert_eq!(repl.execute("dump"), Ok(repl.get_scene().to_string()));
+    }
+
+    #[test]
+    fn steps_and_watches() {
+        let mut repl = repl();
+        assert_eq!(repl.execute("watch 3.out0"), Ok(String::new()));
+        // Watching a pin twice only shows it once
+        assert_eq!(repl.execute("watch 3.out0"), Ok(String::new()));
+        assert_eq!(repl.execute("set 3.0 1"), Ok(String::new()));
+        assert_eq!(
+            repl.execute("step 2"),
+            Ok("Tick 2\n  3.out0 = 0".to_string())
+        );
+        assert_eq!(repl.execute("unwatch 3.out0"), Ok(String::new()));
+        assert_eq!(repl.execute("step"), Ok("Tick 3".to_string()));
+        assert_eq!(
+            repl.execute("step x"),
+            Err("'x' is not a number of ticks".to_string())
+        );
+        assert_eq!(
+            repl.execute("step -1"),
+            Err("'-1' is not a number of ticks".to_string())
+        );
+        assert_eq!(
+            repl.execute("watch 9.0"),
+            Err("There is no pin 9.0".to_string())
+        );
+        assert_eq!(
+            repl.execute("unwatch 9.0"),
+            Err("There is no pin 9.0".to_string())
+        );
+    }
+
+    #[test]
+    fn connects_and_lints() {
+        let mut repl = repl();
+        assert_eq!(repl.execute("disconnect 1.out0"), Ok(String::new()));
+        let lint = repl.execute("lint").unwrap();
+        assert!(lint.contains("1.out0 is not connected to anything"));
+        assert!(lint.contains("2.0 has nothing connected to it"));
+        // Outputs can be written like inputs
+        assert_eq!(repl.execute("connect 1.0 2.0"), Ok(String::new()));
+        let lint = repl.execute("lint").unwrap();
+        assert!(!lint.contains("1.out0"));
+        assert_eq!(
+            repl.execute("connect 9.0 2.0"),
+            Err("9.0 is not an output".to_string())
+        );
+        assert_eq!(
+            repl.execute("disconnect 9.out0"),
+            Err("9.out0 is not an output".to_string())
+        );
+    }
+
+    #[test]
+    fn labels() {
+        let mut repl = repl();
+        assert_eq!(repl.execute("label 1 sum"), Ok(String::new()));
+        assert_eq!(
+            repl.get_scene()
+                .get_component(ComponentID(1))
+                .labels
+                .component,
+            Some("sum".to_string())
+        );
+        assert_eq!(repl.execute("label 1"), Ok(String::new()));
+        assert_eq!(
+            repl.get_scene()
+                .get_component(ComponentID(1))
+                .labels
+                .component,
+            None
+        );
+        // Labels can have spaces, and pins are then found by their names
+        assert_eq!(repl.execute("label 3.0 in x"), Ok(String::new()));
+        assert_eq!(repl.execute("show in_x"), Ok("in_x = 0".to_string()));
+        assert_eq!(
+            repl.execute("label 9 x"),
+            Err("There is no component 9".to_string())
+        );
+        assert_eq!(
+            repl.execute("label 9.0 x"),
+            Err("There is no pin 9.0".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_commands() {
+        let mut repl = repl();
+        assert_eq!(repl.execute(""), Ok(String::new()));
+        assert_eq!(repl.execute("help"), Ok(HELP.to_string()));
+        assert_eq!(
+            repl.execute("frobnicate"),
+            Err("Unknown command 'frobnicate', try help".to_string())
+        );
+        // Commands given the wrong number of arguments are not recognised
+        assert_eq!(
+            repl.execute("step 1 2"),
+            Err("Unknown command 'step', try help".to_string())
+        );
+    }
+}
@@ -0,0 +1,386 @@
+use std::ops::Not;
+
+/// A variable or its negation, the lowest bit is set for negations
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Literal(u32);
+
+impl Literal {
+    fn variable(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    fn is_negated(self) -> bool {
+        self.0 & 1 != 0
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Not for Literal {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(self.0 ^ 1)
+    }
+}
+
+/// A conflict driven clause learning solver, with two watched literals per clause and activity based decisions
+#[derive(Default)]
+pub(crate) struct Solver {
+    clauses: Vec<Vec<Literal>>,
+    units: Vec<Literal>,
+    watches: Vec<Vec<usize>>,
+    assignments: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    phases: Vec<bool>,
+    activities: Vec<f64>,
+    activity_increment: f64,
+    trail: Vec<Literal>,
+    trail_limits: Vec<usize>,
+    propagated: usize,
+    has_empty_clause: bool,
+}
+
+impl Solver {
+    pub(crate) fn new() -> Self {
+        Self {
+            activity_increment: 1.0,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn new_variable(&mut self) -> Literal {
+        let literal = Literal((self.assignments.len() as u32) << 1);
+        self.watches.extend([vec![], vec![]]);
+        self.assignments.push(None);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.phases.push(false);
+        self.activities.push(0.0);
+        literal
+    }
+
+    pub(crate) fn add_clause(&mut self, literals: &[Literal]) {
+        let mut clause = vec![];
+        for &literal in literals {
+            if clause.contains(&!literal) {
+                return;
+            }
+            if !clause.contains(&literal) {
+                clause.push(literal);
+            }
+        }
+        match clause.len() {
+            0 => self.has_empty_clause = true,
+            1 => self.units.push(clause[0]),
+            _ => {
+                self.watches[clause[0].index()].push(self.clauses.len());
+                self.watches[clause[1].index()].push(self.clauses.len());
+                self.clauses.push(clause);
+            }
+        }
+    }
+
+    /// The value of `literal` in the solution found by [`Solver::solve`]
+    pub(crate) fn value(&self, literal: Literal) -> bool {
+        self.assignments[literal.variable()].unwrap_or(false) != literal.is_negated()
+    }
+
+    /// Searches for an assignment satisfying every clause, returning whether there is one
+    pub(crate) fn solve(&mut self) -> bool {
+        if self.has_empty_clause {
+            return false;
+        }
+        for unit in std::mem::take(&mut self.units) {
+            match self.get(unit) {
+                Some(true) => {}
+                Some(false) => return false,
+                None => self.assign(unit, None),
+            }
+        }
+
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.trail_limits.is_empty() {
+                    return false;
+                }
+                let (learnt, level) = self.analyze(conflict);
+                self.backtrack(level);
+                if learnt.len() == 1 {
+                    self.assign(learnt[0], None);
+                } else {
+                    let index = self.clauses.len();
+                    self.watches[learnt[0].index()].push(index);
+                    self.watches[learnt[1].index()].push(index);
+                    self.assign(learnt[0], Some(index));
+                    self.clauses.push(learnt);
+                }
+                self.activity_increment /= 0.95;
+            } else {
+                let Some(variable) = (0..self.assignments.len())
+                    .filter(|&variable| self.assignments[variable].is_none())
+                    .max_by(|&a, &b| self.activities[a].total_cmp(&self.activities[b]))
+                else {
+                    return true;
+                };
+                self.trail_limits.push(self.trail.len());
+                let literal = Literal((variable as u32) << 1);
+                self.assign(
+                    if self.phases[variable] {
+                        literal
+                    } else {
+                        !literal
+                    },
+                    None,
+                );
+            }
+        }
+    }
+
+    fn get(&self, literal: Literal) -> Option<bool> {
+        self.assignments[literal.variable()].map(|value| value != literal.is_negated())
+    }
+
+    fn assign(&mut self, literal: Literal, reason: Option<usize>) {
+        let variable = literal.variable();
+        self.assignments[variable] = Some(!literal.is_negated());
+        self.levels[variable] = self.trail_limits.len();
+        self.reasons[variable] = reason;
+        self.trail.push(literal);
+    }
+
+    // Returns the clause that became false, if any
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagated < self.trail.len() {
+            let false_literal = !self.trail[self.propagated];
+            self.propagated += 1;
+
+            let mut watching = std::mem::take(&mut self.watches[false_literal.index()]);
+            let mut i = 0;
+            while i < watching.len() {
+                let index = watching[i];
+                // Keep the false literal second, so the first is the one that could be implied
+                if self.clauses[index][0] == false_literal {
+                    self.clauses[index].swap(0, 1);
+                }
+                let first = self.clauses[index][0];
+                if self.get(first) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+
+                if let Some(k) = (2..self.clauses[index].len())
+                    .find(|&k| self.get(self.clauses[index][k]) != Some(false))
+                {
+                    self.clauses[index].swap(1, k);
+                    self.watches[self.clauses[index][1].index()].push(index);
+                    watching.swap_remove(i);
+                    continue;
+                }
+
+                if self.get(first) == Some(false) {
+                    self.watches[false_literal.index()] = watching;
+                    self.propagated = self.trail.len();
+                    return Some(index);
+                }
+                self.assign(first, Some(index));
+                i += 1;
+            }
+            self.watches[false_literal.index()] = watching;
+        }
+        None
+    }
+
+    // Learns a clause from the first unique implication point, returning it and the level to jump back to
+    fn analyze(&mut self, conflict: usize) -> (Vec<Literal>, usize) {
+        let level = self.trail_limits.len();
+        let mut seen = vec![false; self.assignments.len()];
+        let mut learnt = vec![Literal(0)];
+        let mut at_this_level = 0;
+        let mut clause = conflict;
+        let mut skip = 0;
+        let mut index = self.trail.len();
+        let implied = loop {
+            for k in skip..self.clauses[clause].len() {
+                let literal = self.clauses[clause][k];
+                let variable = literal.variable();
+                if !seen[variable] && self.levels[variable] > 0 {
+                    seen[variable] = true;
+                    self.bump(variable);
+                    if self.levels[variable] == level {
+                        at_this_level += 1;
+                    } else {
+                        learnt.push(literal);
+                    }
+                }
+            }
+            // Reason clauses have the literal they implied first
+            skip = 1;
+
+            index -= 1;
+            while !seen[self.trail[index].variable()] {
+                index -= 1;
+            }
+            let literal = self.trail[index];
+            seen[literal.variable()] = false;
+            at_this_level -= 1;
+            if at_this_level == 0 {
+                break literal;
+            }
+            clause = self.reasons[literal.variable()].unwrap();
+        };
+        learnt[0] = !implied;
+
+        let mut backjump_level = 0;
+        for k in 1..learnt.len() {
+            let level = self.levels[learnt[k].variable()];
+            if level > backjump_level {
+                backjump_level = level;
+                learnt.swap(1, k);
+            }
+        }
+        (learnt, backjump_level)
+    }
+
+    fn bump(&mut self, variable: usize) {
+        self.activities[variable] += self.activity_increment;
+        if self.activities[variable] > 1e100 {
+            for activity in &mut self.activities {
+                *activity *= 1e-100;
+            }
+            self.activity_increment *= 1e-100;
+        }
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if let Some(&limit) = self.trail_limits.get(level) {
+            for literal in self.trail.drain(limit..) {
+                let variable = literal.variable();
+                self.phases[variable] = !literal.is_negated();
+                self.assignments[variable] = None;
+                self.reasons[variable] = None;
+            }
+            self.trail_limits.truncate(level);
+            self.propagated = self.trail.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Clauses are written with variables counting from 1 and negative numbers for negations, like DIMACS
+    fn solve(variable_count: usize, clauses: &[&[i32]]) -> Option<Vec<bool>> {
+        let mut solver = Solver::new();
+        let variables = (0..variable_count)
+            .map(|_| solver.new_variable())
+            .collect::<Vec<_>>();
+        let literal = |number: i32| {
+            let variable = variables[number.unsigned_abs() as usize - 1];
+            if number < 0 {
+                !variable
+            } else {
+                variable
+            }
+        };
+        for clause in clauses {
+            solver.add_clause(
+                &clause
+                    .iter()
+                    .map(|&number| literal(number))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        solver.solve().then(|| {
+            variables
+                .iter()
+                .map(|&variable| solver.value(variable))
+                .collect()
+        })
+    }
+
+    fn satisfies(assignment: &[bool], clauses: &[&[i32]]) -> bool {
+        clauses.iter().all(|clause| {
+            clause
+                .iter()
+                .any(|&number| assignment[number.unsigned_abs() as usize - 1] == (number > 0))
+        })
+    }
+
+    #[test]
+    fn satisfiable() {
+        let clauses: &[&[i32]] = &[&[1, 2], &[-1, 3], &[-2, -3], &[2, 3, -4], &[4]];
+        let assignment = solve(4, clauses).unwrap();
+        assert!(satisfies(&assignment, clauses));
+
+        assert_eq!(solve(2, &[]), Some(vec![false, false]));
+        assert_eq!(solve(1, &[&[1, -1]]), Some(vec![false]));
+    }
+
+    #[test]
+    fn unsatisfiable() {
+        assert_eq!(solve(1, &[&[1], &[-1]]), None);
+        assert_eq!(solve(0, &[&[]]), None);
+        assert_eq!(solve(2, &[&[1, 2], &[1, -2], &[-1, 2], &[-1, -2]]), None);
+
+        // Three pigeons do not fit in two holes, where variable 2p + h + 1 puts pigeon p in hole h
+        let mut clauses = vec![];
+        for pigeon in 0..3 {
+            clauses.push(vec![2 * pigeon + 1, 2 * pigeon + 2]);
+        }
+        for hole in 1..=2 {
+            for a in 0..3 {
+                for b in a + 1..3 {
+                    clauses.push(vec![-(2 * a + hole), -(2 * b + hole)]);
+                }
+            }
+        }
+        let clauses = clauses.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        assert_eq!(solve(6, &clauses), None);
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        const VARIABLES: usize = 8;
+
+        let mut random = 0x2545F4914F6CDD1Du64;
+        let mut next = |n: u64| {
+            random ^= random << 13;
+            random ^= random >> 7;
+            random ^= random << 17;
+            random % n
+        };
+        for _ in 0..300 {
+            let clauses = (0..30 + next(10))
+                .map(|_| {
+                    (0..3)
+                        .map(|_| {
+                            let variable = next(VARIABLES as u64) as i32 + 1;
+                            if next(2) == 0 {
+                                -variable
+                            } else {
+                                variable
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            let clauses = clauses.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+            let satisfiable = (0..1 << VARIABLES).any(|bits| {
+                let assignment = (0..VARIABLES)
+                    .map(|i| bits & (1 << i) != 0)
+                    .collect::<Vec<_>>();
+                satisfies(&assignment, &clauses)
+            });
+            match solve(VARIABLES, &clauses) {
+                Some(assignment) => assert!(satisfies(&assignment, &clauses)),
+                None => assert!(!satisfiable, "{clauses:?} is satisfiable"),
+            }
+        }
+    }
+}
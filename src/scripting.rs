@@ -0,0 +1,593 @@
+use std::{cell::RefCell, rc::Rc};
+
+use derive_more::Display;
+use raylib::math::Vector2;
+use rhai::{Engine, EvalAltResult, NativeCallContext, Scope, FLOAT, INT};
+
+use crate::{
+    auto_layout, AssertCondition, Component, ComponentID, ComponentType, Input, Mismatch, Pin,
+    Scene, Stability, Violation, MAX_PORT_WIDTH,
+};
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt = "{_0}")]
+pub struct ScriptError(String);
+
+/// Everything a script reported while it ran
+#[derive(Clone, Debug, Default)]
+pub struct ScriptReport {
+    /// Lines passed to `print`
+    pub output: Vec<String>,
+    /// Calls to `expect` and `expect_port` that did not hold, with the line of the script they were on
+    pub mismatches: Vec<Mismatch>,
+    pub violations: Vec<Violation>,
+}
+
+#[derive(Default)]
+struct ScriptState {
+    scene: Scene,
+    unplaced: Vec<ComponentID>,
+    report: ScriptReport,
+    ticks: usize,
+}
+
+impl ScriptState {
+    // Counts ticks towards `MAX_TICKS`, failing without counting them if there are not that many left
+    fn use_ticks(&mut self, ticks: usize) -> Result<(), Box<EvalAltResult>> {
+        if ticks > MAX_TICKS - self.ticks {
+            return Err(
+                format!("Scripts can only run {MAX_TICKS} ticks each time they are run").into(),
+            );
+        }
+        self.ticks += ticks;
+        Ok(())
+    }
+}
+
+/// The most operations a script can run before it is stopped, so that a script stuck in a loop cannot hang the editor.
+/// Calls that simulate the scene only count as one operation however many ticks they run, so those are limited by
+/// [`MAX_TICKS`] instead
+const MAX_OPERATIONS: u64 = 100_000_000;
+
+/// The most ticks a script can simulate each time it is run
+const MAX_TICKS: usize = 1_000_000;
+
+/// Runs [Rhai](https://rhai.rs) scripts against a scene. Variables are kept from one run to the next.
+///
+/// Components are referred to by their ids and pins by strings written the way they are displayed or by their name,
+/// so `connect(out(a, 0), pin(b, 1))` connects the first output of `a` to the second input of `b`.
+/// Scripts can call
+///
+/// - `add(kind)` and `add(kind, x, y)` to add a `"Not"`, `"Or"` or `"Delay"`, returning its id.
+///   Components added without a position are laid out once the script finishes
+/// - `add_assert(condition, inputs)` to add an assert that is `"always_high"`, `"never_high"` or `"equal"`
+/// - `add_tunnel(name)` to add a tunnel, which is connected to every other tunnel with the same name
+/// - `add_input_port(name, width)` and `add_output_port(name, width)` to add the circuit's inputs and outputs
+/// - `label(id, text)` and `label(pin, text)` to label a component or a pin, or remove its label when `text` is empty
+/// - `pin(id, index)` and `out(id, index)` for the name of an input or an output
+/// - `connect(output, input)` and `disconnect(output)`
+/// - `set(pin, state)` on inputs that nothing is connected to, and `get(pin)`
+/// - `update()` and `step(ticks)`, which return whether anything changed, and `tick()`
+/// - `run_until_stable(max_ticks)`, which describes how the scene settled
+/// - `expect(pin, state)`, which records a mismatch when the pin is in the other state
+/// - `set_port(name, value)`, `get_port(name)` and `expect_port(name, value)`, which do the same for ports.
+///   Port values cannot be negative or wider than the port, and `get_port` fails on values too large for a script's
+///   integers
+///
+/// Each run can simulate at most a million ticks between all of its calls to `update`, `step` and `run_until_stable`
+pub struct ScriptEngine {
+    engine: Engine,
+    scope: Scope<'static>,
+    state: Rc<RefCell<ScriptState>>,
+}
+
+impl ScriptEngine {
+    pub fn new() -> Self {
+        let state = Rc::new(RefCell::new(ScriptState::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        {
+            let state = state.clone();
+            engine.on_print(move |text| state.borrow_mut().report.output.push(text.to_string()));
+        }
+
+        {
+            let state = state.clone();
+            engine.register_fn("add", move |kind: &str| {
+                let mut state = state.borrow_mut();
+                let id = state.scene.add_component(Component {
+                    typ: component_type(kind)?,
+                    position: Vector2::zero(),
+                    labels: Default::default(),
+                });
+                state.unplaced.push(id);
+                Ok::<_, Box<EvalAltResult>>(id.0 as INT)
+            });
+        }
+        {
+            let state = state.clone();
+            engine.register_fn("add", move |kind: &str, x: FLOAT, y: FLOAT| {
+                let id = state.borrow_mut().scene.add_component(Component {
+                    typ: component_type(kind)?,
+                    position: Vector2::new(x as f32, y as f32),
+                    labels: Default::default(),
+                });
+                Ok::<_, Box<EvalAltResult>>(id.0 as INT)
+            });
+        }
+        {
+            let state = state.clone();
+            engine.register_fn("add", move |kind: &str, x: INT, y: INT| {
+                let id = state.borrow_mut().scene.add_component(Component {
+                    typ: component_type(kind)?,
+                    position: Vector2::new(x as f32, y as f32),
+                    labels: Default::default(),
+                });
+                Ok::<_, Box<EvalAltResult>>(id.0 as INT)
+            });
+        }
+        {
+            let state = state.clone();
+            engine.register_fn("add_assert", move |condition: &str, inputs: INT| {
+                let condition = match condition {
+                    "always_high" => AssertCondition::AlwaysHigh,
+                    "never_high" => AssertCondition::NeverHigh,
+                    "equal" => AssertCondition::Equal,
+                    _ => return Err(format!("Unknown condition '{condition}'").into()),
+                };
+                if inputs < 1 {
+                    return Err("Asserts need at least one input".into());
+                }
+                let mut state = state.borrow_mut();
+                let id = state.scene.add_component(Component {
+                    typ: ComponentType::Assert {
+                        inputs: vec![Input { state: false }; inputs as usize],
+                        condition,
+                    },
+                    position: Vector2::zero(),
+                    labels: Default::default(),
+                });
+                state.unplaced.push(id);
+                Ok::<_, Box<EvalAltResult>>(id.0 as INT)
+            });
+        }
+
+        {
+            let state = state.clone();
+            engine.register_fn("add_tunnel", move |name: &str| {
+                let mut state = state.borrow_mut();
+                let id = state.scene.add_component(Component {
+                    typ: ComponentType::Tunnel {
+                        name: name.to_string(),
+                        input: Input { state: false },
+                        output: None,
+                    },
+                    position: Vector2::zero(),
+                    labels: Default::default(),
+                });
+                state.unplaced.push(id);
+                id.0 as INT
+            });
+        }
+        for (function, input) in [("add_input_port", true), ("add_output_port", false)] {
+            let state = state.clone();
+            engine.register_fn(function, move |name: &str, width: INT| {
+                let width = usize::try_from(width)
+                    .ok()
+                    .filter(|width| (1..=MAX_PORT_WIDTH).contains(width))
+                    .ok_or_else(|| format!("Ports are 1 to {MAX_PORT_WIDTH} bits wide"))?;
+                let name = name.to_string();
+                let typ = if input {
+                    ComponentType::InputPort {
+                        name,
+                        outputs: vec![None; width],
+                        value: 0,
+                    }
+                } else {
+                    ComponentType::OutputPort {
+                        name,
+                        inputs: vec![Input { state: false }; width],
+                    }
+                };
+                let mut state = state.borrow_mut();
+                let id = state.scene.add_component(Component {
+                    typ,
+                    position: Vector2::zero(),
+                    labels: Default::default(),
+                });
+                state.unplaced.push(id);
+                Ok::<_, Box<EvalAltResult>>(id.0 as INT)
+            });
+        }
+        {
+            let state = state.clone();
+            engine.register_fn(
+                "label",
+                move |id: INT, label: &str| -> Result<(), Box<EvalAltResult>> {
+                    let mut state = state.borrow_mut();
+                    let id = find_component(&state.scene, id)?;
+                    state.scene.get_component_mut(id).labels.component =
+                        (!label.is_empty()).then(|| label.to_string());
+                    Ok(())
+                },
+            );
+        }
+        {
+            let state = state.clone();
+            engine.register_fn(
+                "label",
+                move |name: &str, label: &str| -> Result<(), Box<EvalAltResult>> {
+                    let mut state = state.borrow_mut();
+                    let pin = find_pin(&state.scene, name)?;
+                    state
+                        .scene
+                        .set_pin_label(pin, (!label.is_empty()).then(|| label.to_string()));
+                    Ok(())
+                },
+            );
+        }
+
+        engine.register_fn("pin", |id: INT, index: INT| format!("{id}.{index}"));
+        engine.register_fn("out", |id: INT, index: INT| format!("{id}.out{index}"));
+
+        {
+            let state = state.clone();
+            engine.register_fn(
+                "connect",
+                move |output: &str, input: &str| -> Result<(), Box<EvalAltResult>> {
+                    let mut state = state.borrow_mut();
+                    let output = find_output(&state.scene, output)?;
+                    let input = find_pin(&state.scene, input)?;
+                    state
+                        .scene
+                        .connect(output, input)
+                        .map_err(|error| error.to_string().into())
+                },
+            );
+        }
+        {
+            let state = state.clone();
+            engine.register_fn(
+                "disconnect",
+                move |output: &str| -> Result<(), Box<EvalAltResult>> {
+                    let mut state = state.borrow_mut();
+                    let output = find_output(&state.scene, output)?;
+                    state
+                        .scene
+                        .disconnect(output)
+                        .map_err(|error| error.to_string().into())
+                },
+            );
+        }
+        {
+            let state = state.clone();
+            engine.register_fn(
+                "set",
+                move |name: &str, value: bool| -> Result<(), Box<EvalAltResult>> {
+                    let mut state = state.borrow_mut();
+                    match find_pin(&state.scene, name)? {
+                        Pin::Input(id, index) if state.scene.get_driver(id, index).is_none() => {
+                            state.scene.get_component_mut(id).get_inputs_mut()[index].state = value;
+                            Ok(())
+                        }
+                        _ => Err(
+                            format!("{name} is not an input that nothing is connected to").into(),
+                        ),
+                    }
+                },
+            );
+        }
+        {
+            let state = state.clone();
+            engine.register_fn("get", move |name: &str| {
+                let state = state.borrow();
+                Ok::<_, Box<EvalAltResult>>(
+                    state.scene.get_pin_state(find_pin(&state.scene, name)?),
+                )
+            });
+        }
+
+        {
+            let state = state.clone();
+            engine.register_fn("update", move || {
+                let mut state = state.borrow_mut();
+                state.use_ticks(1)?;
+                let update = state.scene.update();
+                state.report.violations.extend(update.violations);
+                Ok::<_, Box<EvalAltResult>>(update.had_changes)
+            });
+        }
+        {
+            let state = state.clone();
+            engine.register_fn("step", move |ticks: INT| {
+                let mut state = state.borrow_mut();
+                let ticks = usize::try_from(ticks).unwrap_or(0);
+                state.use_ticks(ticks)?;
+                let update = state.scene.step(ticks);
+                state.report.violations.extend(update.violations);
+                Ok::<_, Box<EvalAltResult>>(update.had_changes)
+            });
+        }
+        {
+            let state = state.clone();
+            engine.register_fn("tick", move || state.borrow().scene.tick() as INT);
+        }
+        {
+            let state = state.clone();
+            engine.register_fn("run_until_stable", move |max_ticks: INT| {
+                let mut state = state.borrow_mut();
+                // Only the ticks that are run count, but running out of them is an error rather than a timeout
+                let max_ticks = usize::try_from(max_ticks).unwrap_or(0);
+                let remaining = MAX_TICKS - state.ticks;
+                let start = state.scene.tick();
+                let stability = state.scene.run_until_stable(max_ticks.min(remaining));
+                let ticks = state.scene.tick() - start;
+                state.use_ticks(ticks)?;
+                if matches!(stability, Stability::TimedOut) && max_ticks > remaining {
+                    state.use_ticks(remaining + 1)?;
+                }
+                Ok::<_, Box<EvalAltResult>>(stability.to_string())
+            });
+        }
+        {
+            let state = state.clone();
+            engine.register_fn(
+                "expect",
+                move |context: NativeCallContext, name: &str, expected: bool| {
+                    let mut state = state.borrow_mut();
+                    let actual = state.scene.get_pin_state(find_pin(&state.scene, name)?);
+                    if actual != expected {
+                        let mismatch = Mismatch {
+                            line: context.call_position().line().unwrap_or(0),
+                            tick: state.scene.tick(),
+                            pin: name.to_string(),
+                            expected: expected as u64,
+                            actual: actual as u64,
+                        };
+                        state.report.mismatches.push(mismatch);
+                    }
+                    Ok::<_, Box<EvalAltResult>>(())
+                },
+            );
+        }
+        {
+            let state = state.clone();
+            engine.register_fn(
+                "set_port",
+                move |name: &str, value: INT| -> Result<(), Box<EvalAltResult>> {
+                    state
+                        .borrow_mut()
+                        .scene
+                        .set_port(name, port_value(name, value)?)
+                        .map_err(|error| error.to_string().into())
+                },
+            );
+        }
+        {
+            let state = state.clone();
+            engine.register_fn(
+                "get_port",
+                move |name: &str| -> Result<INT, Box<EvalAltResult>> {
+                    let state = state.borrow();
+                    let value = state
+                        .scene
+                        .get_port(name)
+                        .map_err(|error| error.to_string())?;
+                    INT::try_from(value).map_err(|_| {
+                        format!("Port {name} is {value}, which is too large for a script").into()
+                    })
+                },
+            );
+        }
+        {
+            let state = state.clone();
+            engine.register_fn(
+                "expect_port",
+                move |context: NativeCallContext, name: &str, expected: INT| {
+                    let mut state = state.borrow_mut();
+                    let expected = port_value(name, expected)?;
+                    let actual = state
+                        .scene
+                        .get_port(name)
+                        .map_err(|error| error.to_string())?;
+                    if actual != expected {
+                        let mismatch = Mismatch {
+                            line: context.call_position().line().unwrap_or(0),
+                            tick: state.scene.tick(),
+                            pin: name.to_string(),
+                            expected,
+                            actual,
+                        };
+                        state.report.mismatches.push(mismatch);
+                    }
+                    Ok::<_, Box<EvalAltResult>>(())
+                },
+            );
+        }
+
+        Self {
+            engine,
+            scope: Scope::new(),
+            state,
+        }
+    }
+
+    /// Runs `script` on `scene`, laying out any components it added without a position around `position`.
+    /// Whatever the script did before an error is kept
+    pub fn run(
+        &mut self,
+        scene: &mut Scene,
+        script: &str,
+        position: Vector2,
+    ) -> Result<ScriptReport, ScriptError> {
+        {
+            let mut state = self.state.borrow_mut();
+            state.scene = std::mem::take(scene);
+            state.ticks = 0;
+        }
+        let result = self.engine.run_with_scope(&mut self.scope, script);
+
+        let mut state = self.state.borrow_mut();
+        *scene = std::mem::take(&mut state.scene);
+        auto_layout(scene, &std::mem::take(&mut state.unplaced), position);
+        let report = std::mem::take(&mut state.report);
+        result.map_err(|error| ScriptError(error.to_string()))?;
+        Ok(report)
+    }
+}
+
+impl Default for ScriptEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn port_value(name: &str, value: INT) -> Result<u64, Box<EvalAltResult>> {
+    u64::try_from(value)
+        .map_err(|_| format!("Port {name} cannot be given {value}, which is negative").into())
+}
+
+fn component_type(kind: &str) -> Result<ComponentType, Box<EvalAltResult>> {
+    match kind {
+        "Not" => Ok(ComponentType::Not {
+            input: Input { state: false },
+            output: None,
+        }),
+        "Or" => Ok(ComponentType::Or {
+            inputs: [Input { state: false }; 2],
+            output: None,
+        }),
+        "Delay" => Ok(ComponentType::Delay {
+            input: Input { state: false },
+            output: None,
+            state_last_frame: false,
+        }),
+        _ => Err(format!(
+            "Unknown component '{kind}', asserts, tunnels and ports have their own add functions"
+        )
+        .into()),
+    }
+}
+
+fn find_component(scene: &Scene, id: INT) -> Result<ComponentID, Box<EvalAltResult>> {
+    usize::try_from(id)
+        .ok()
+        .filter(|&id| id < scene.into_iter().len())
+        .map(ComponentID)
+        .ok_or_else(|| format!("There is no component {id}").into())
+}
+
+fn find_pin(scene: &Scene, name: &str) -> Result<Pin, Box<EvalAltResult>> {
+    scene
+        .find_pin(name)
+        .ok_or_else(|| format!("There is no pin {name}").into())
+}
+
+fn find_output(scene: &Scene, name: &str) -> Result<Pin, Box<EvalAltResult>> {
+    scene
+        .find_output(name)
+        .ok_or_else(|| format!("{name} is not an output").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CircuitBuilder;
+
+    fn run(scene: &mut Scene, script: &str) -> Result<ScriptReport, ScriptError> {
+        ScriptEngine::new().run(scene, script, Vector2::zero())
+    }
+
+    #[test]
+    fn runs_until_stable() {
+        let mut scene = Scene::new();
+        let report = run(
+            &mut scene,
+            r#"
+            let a = add("Or");
+            let b = add("Not");
+            connect(out(a, 0), pin(b, 0));
+            print(run_until_stable(10));
+            set(pin(a, 0), true);
+            print(run_until_stable(10));
+            expect(out(b, 0), true);
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            report.output,
+            ["Stable after 1 ticks", "Stable after 1 ticks"]
+        );
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].line, 8);
+        assert!(report.violations.is_empty());
+    }
+
+    #[test]
+    fn limits_ticks() {
+        let mut engine = ScriptEngine::new();
+        let mut scene = Scene::new();
+        let error = |engine: &mut ScriptEngine, scene: &mut Scene, script: &str| {
+            engine
+                .run(scene, script, Vector2::zero())
+                .unwrap_err()
+                .to_string()
+        };
+        let limit = "Scripts can only run 1000000 ticks each time they are run";
+
+        assert!(error(&mut engine, &mut scene, "step(1000000); update();").contains(limit));
+        assert_eq!(scene.tick(), 1_000_000);
+        // The count starts again on each run, and ticks that would go over it are not run
+        assert!(error(&mut engine, &mut scene, "update(); step(1000000);").contains(limit));
+        assert_eq!(scene.tick(), 1_000_001);
+
+        // Delays take a tick each to pass the input on, so the chain settles after three ticks
+        let chain = r#"
+            let a = add("Delay");
+            let b = add("Delay");
+            connect(out(a, 0), pin(b, 0));
+            step(999990 + extra);
+            set(pin(a, 0), true);
+            print(run_until_stable(10));
+        "#;
+        let report = engine
+            .run(
+                &mut scene,
+                &format!("let extra = 0;{chain}"),
+                Vector2::zero(),
+            )
+            .unwrap();
+        assert_eq!(report.output, ["Stable after 3 ticks"]);
+        let mut scene = Scene::new();
+        assert!(error(&mut engine, &mut scene, &format!("let extra = 9;{chain}")).contains(limit));
+        assert_eq!(scene.tick(), 1_000_000);
+    }
+
+    #[test]
+    fn checks_port_values() {
+        let mut b = CircuitBuilder::new();
+        let a = b.input_port("a", 64);
+        b.output_port("y", a);
+        let mut scene = b.build().scene;
+        let error = |scene: &mut Scene, script: &str| run(scene, script).unwrap_err().to_string();
+
+        assert!(error(&mut scene, r#"set_port("a", -1);"#)
+            .contains("Port a cannot be given -1, which is negative"));
+        assert!(error(&mut scene, r#"expect_port("y", -2);"#)
+            .contains("Port y cannot be given -2, which is negative"));
+
+        let report = run(
+            &mut scene,
+            r#"set_port("a", 5); update(); print(get_port("y")); expect_port("y", 4);"#,
+        )
+        .unwrap();
+        assert_eq!(report.output, ["5"]);
+        assert_eq!(report.mismatches[0].actual, 5);
+
+        scene.set_port("a", u64::MAX).unwrap();
+        scene.run_until_stable(10);
+        assert!(error(&mut scene, r#"get_port("y");"#)
+            .contains("Port y is 18446744073709551615, which is too large for a script"));
+    }
+}
@@ -0,0 +1,263 @@
+use raylib::math::Vector2;
+
+use crate::{
+    auto_layout, Component, ComponentID, ComponentType, Expression, Input, Output, Pin, Scene,
+};
+
+/// The components made for a set of expressions, and the pins to drive and read.
+///
+/// Outputs can only drive one input, so every use of a variable gets its own input pin
+pub struct Synthesis {
+    components: Vec<ComponentID>,
+    inputs: Vec<(String, Vec<Pin>)>,
+    outputs: Vec<(String, Pin)>,
+}
+
+enum Source {
+    Pin(Pin),
+    Variable(String),
+}
+
+impl Synthesis {
+    /// Builds a new scene for `outputs`
+    pub fn new_scene(outputs: &[(String, Expression)]) -> (Scene, Self) {
+        let mut scene = Scene::new();
+        let synthesis = Self::insert(&mut scene, outputs, Vector2::zero());
+        (scene, synthesis)
+    }
+
+    /// Adds components computing each of `outputs` to `scene`, laid out around `position`.
+    /// `And` is made from `Or` and `Not` gates, an `Or` with a floating input passes a signal through,
+    /// and constants come from gates with nothing connected
+    pub fn insert(scene: &mut Scene, outputs: &[(String, Expression)], position: Vector2) -> Self {
+        let mut synthesis = Self {
+            components: vec![],
+            inputs: vec![],
+            outputs: vec![],
+        };
+        for (name, expression) in outputs {
+            let pin = match synthesis.build(scene, expression) {
+                Source::Pin(pin) => pin,
+                Source::Variable(variable) => {
+                    let buffer = synthesis.add(scene, or_gate());
+                    synthesis.connect(scene, Source::Variable(variable), Pin::Input(buffer, 0));
+                    Pin::Output(buffer, 0)
+                }
+            };
+            synthesis.outputs.push((name.clone(), pin));
+        }
+        auto_layout(scene, &synthesis.components, position);
+        synthesis
+    }
+
+    pub fn get_components(&self) -> &[ComponentID] {
+        &self.components
+    }
+
+    /// Each variable and every input pin that reads it
+    pub fn get_inputs(&self) -> &[(String, Vec<Pin>)] {
+        &self.inputs
+    }
+
+    pub fn get_outputs(&self) -> &[(String, Pin)] {
+        &self.outputs
+    }
+
+    fn add(&mut self, scene: &mut Scene, typ: ComponentType) -> ComponentID {
+        let id = scene.add_component(Component {
+            typ,
+            position: Vector2::zero(),
+            labels: Default::default(),
+        });
+        self.components.push(id);
+        id
+    }
+
+    fn connect(&mut self, scene: &mut Scene, source: Source, input: Pin) {
+        let Pin::Input(component, index) = input else {
+            unreachable!()
+        };
+        match source {
+            Source::Pin(Pin::Output(id, output_index)) => {
+                scene.get_component_mut(id).get_outputs_mut()[output_index] =
+                    Some(Output { component, index });
+            }
+            Source::Pin(Pin::Input(..)) => unreachable!(),
+            Source::Variable(name) => {
+                match self.inputs.iter_mut().find(|(other, _)| *other == name) {
+                    Some((_, pins)) => pins.push(input),
+                    None => self.inputs.push((name, vec![input])),
+                }
+            }
+        }
+    }
+
+    fn build(&mut self, scene: &mut Scene, expression: &Expression) -> Source {
+        match expression {
+            Expression::Constant(false) => Source::Pin(Pin::Output(self.add(scene, or_gate()), 0)),
+            Expression::Constant(true) => Source::Pin(Pin::Output(self.add(scene, not_gate()), 0)),
+            Expression::Variable(name) => Source::Variable(name.clone()),
+            Expression::Not(operand) => self.build_negated(scene, operand),
+            Expression::Or(operands) => self.build_or(scene, operands),
+            Expression::And(operands) => self.build_and(scene, operands),
+        }
+    }
+
+    fn build_negated(&mut self, scene: &mut Scene, expression: &Expression) -> Source {
+        if let Expression::Not(operand) = expression {
+            return self.build(scene, operand);
+        }
+        let not = self.add(scene, not_gate());
+        let source = self.build(scene, expression);
+        self.connect(scene, source, Pin::Input(not, 0));
+        Source::Pin(Pin::Output(not, 0))
+    }
+
+    // Operands are split in half so the tree of gates stays shallow
+    fn build_or(&mut self, scene: &mut Scene, operands: &[Expression]) -> Source {
+        match operands {
+            [] => self.build(scene, &Expression::Constant(false)),
+            [operand] => self.build(scene, operand),
+            _ => {
+                let or = self.add(scene, or_gate());
+                let (left, right) = operands.split_at(operands.len() / 2);
+                for (index, half) in [left, right].into_iter().enumerate() {
+                    let source = self.build_or(scene, half);
+                    self.connect(scene, source, Pin::Input(or, index));
+                }
+                Source::Pin(Pin::Output(or, 0))
+            }
+        }
+    }
+
+    // a & b is built as !(!a | !b)
+    fn build_and(&mut self, scene: &mut Scene, operands: &[Expression]) -> Source {
+        match operands {
+            [] => self.build(scene, &Expression::Constant(true)),
+            [operand] => self.build(scene, operand),
+            _ => {
+                let not = self.add(scene, not_gate());
+                let source = self.build_nand(scene, operands);
+                self.connect(scene, source, Pin::Input(not, 0));
+                Source::Pin(Pin::Output(not, 0))
+            }
+        }
+    }
+
+    fn build_nand(&mut self, scene: &mut Scene, operands: &[Expression]) -> Source {
+        match operands {
+            [operand] => self.build_negated(scene, operand),
+            _ => {
+                let or = self.add(scene, or_gate());
+                let (left, right) = operands.split_at(operands.len() / 2);
+                for (index, half) in [left, right].into_iter().enumerate() {
+                    let source = self.build_nand(scene, half);
+                    self.connect(scene, source, Pin::Input(or, index));
+                }
+                Source::Pin(Pin::Output(or, 0))
+            }
+        }
+    }
+}
+
+pub(crate) fn not_gate() -> ComponentType {
+    ComponentType::Not {
+        input: Input { state: false },
+        output: None,
+    }
+}
+
+pub(crate) fn or_gate() -> ComponentType {
+    ComponentType::Or {
+        inputs: [Input { state: false }, Input { state: false }],
+        output: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Diagnostic, Stability, TruthTable};
+
+    // Checks every combination of the inputs against `expected`, which is given the state of each input in order
+    fn check(outputs: &[(String, Expression)], expected: impl Fn(&[bool]) -> Vec<bool>) {
+        let (mut scene, synthesis) = Synthesis::new_scene(outputs);
+        let overlapping = scene
+            .lint()
+            .into_iter()
+            .filter(|diagnostic| matches!(diagnostic, Diagnostic::Overlapping(..)))
+            .collect::<Vec<_>>();
+        assert_eq!(overlapping, []);
+
+        let inputs = synthesis.get_inputs();
+        for combination in 0..1 << inputs.len() {
+            let states = (0..inputs.len())
+                .map(|i| combination & (1 << i) != 0)
+                .collect::<Vec<_>>();
+            for ((_, pins), &state) in inputs.iter().zip(&states) {
+                for &pin in pins {
+                    let Pin::Input(id, index) = pin else {
+                        unreachable!()
+                    };
+                    scene.get_component_mut(id).get_inputs_mut()[index].state = state;
+                }
+            }
+            assert!(matches!(
+                scene.run_until_stable(100),
+                Stability::Stable { .. }
+            ));
+            let actual = synthesis
+                .get_outputs()
+                .iter()
+                .map(|&(_, pin)| scene.get_pin_state(pin))
+                .collect::<Vec<_>>();
+            assert_eq!(actual, expected(&states), "inputs {states:?}");
+        }
+    }
+
+    #[test]
+    fn matches_truth_tables() {
+        let table = TruthTable::parse(
+            "a,b,c,sum,carry\n\
+             0,0,0,0,0\n0,0,1,1,0\n0,1,0,1,0\n0,1,1,0,1\n\
+             1,0,0,1,0\n1,0,1,0,1\n1,1,0,0,1\n1,1,1,1,1\n",
+        )
+        .unwrap();
+        let outputs = table.to_expressions().unwrap();
+        let (_, synthesis) = Synthesis::new_scene(&outputs);
+        let names = synthesis
+            .get_inputs()
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        check(&outputs, |states| {
+            let row = table.get_inputs().iter().fold(0, |row, name| {
+                let i = names.iter().position(|other| other == name).unwrap();
+                row << 1 | states[i] as usize
+            });
+            table.get_rows()[row].1.clone()
+        });
+    }
+
+    #[test]
+    fn synthesizes_constants_and_variables() {
+        let outputs = ["(a & !b) | c", "1", "0", "a", "!!c"]
+            .iter()
+            .enumerate()
+            .map(|(i, text)| (format!("y{i}"), text.parse::<Expression>().unwrap()))
+            .collect::<Vec<_>>();
+        let (_, synthesis) = Synthesis::new_scene(&outputs);
+        assert_eq!(
+            synthesis
+                .get_inputs()
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+        check(&outputs, |states| {
+            let [a, b, c] = [states[0], states[1], states[2]];
+            vec![a && !b || c, true, false, a, c]
+        });
+    }
+}
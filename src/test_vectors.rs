@@ -0,0 +1,260 @@
+use std::{collections::HashMap, str::FromStr};
+
+use derive_more::Display;
+
+use crate::{parse_port_value, Pin, PortDirection, PortError, Scene};
+
+#[derive(Clone, Debug, Display)]
+pub enum TestVectorError {
+    #[display(fmt = "Line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[display(fmt = "Line {line}: There is no pin or port {pin}")]
+    NoSuchPin { line: usize, pin: String },
+    #[display(
+        fmt = "Line {line}: {pin} is not an input that nothing is connected to, so it cannot be set"
+    )]
+    NotSettable { line: usize, pin: String },
+    #[display(fmt = "Line {line}: {pin} is a pin, so it can only be 0 or 1")]
+    NotABit { line: usize, pin: String },
+    #[display(fmt = "Line {line}: {error}")]
+    Port { line: usize, error: PortError },
+}
+
+/// An expectation that did not hold
+#[derive(Clone, Debug, Display)]
+#[display(fmt = "Tick {tick}: {pin} was {actual} but {expected} was expected (line {line})")]
+pub struct Mismatch {
+    pub line: usize,
+    pub tick: usize,
+    /// The pin or port, as it was written
+    pub pin: String,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Set,
+    Expect,
+}
+
+#[derive(Clone)]
+struct Step {
+    line: usize,
+    tick: usize,
+    action: Action,
+    pin: String,
+    value: u64,
+}
+
+// What the name in a step refers to, where ports are looked for before pins
+enum Target {
+    Pin(Pin),
+    Port(String),
+}
+
+/// Timed stimulus and expected states for the pins of a scene, read from text like
+///
+/// ```text
+/// # Comments start with a hash
+/// pin a = 0.1
+/// pin q = Not_2_out0
+/// 0 set a 1
+/// 3 expect q 0
+/// 5 set sum 0x1f
+/// ```
+///
+/// Pins are written the way they are displayed, by their name, or by an alias from a `pin` line.
+/// Ports are written by their name and can be given any value that fits in them, in decimal, `0b` binary or `0x` hex.
+/// On each tick the expectations are checked first, then the inputs are set, and then the scene is updated
+#[derive(Clone)]
+pub struct TestVectors {
+    aliases: HashMap<String, String>,
+    steps: Vec<Step>,
+}
+
+impl FromStr for TestVectors {
+    type Err = TestVectorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut aliases = HashMap::new();
+        let mut steps = vec![];
+        for (i, line) in s.lines().enumerate() {
+            let line_number = i + 1;
+            let parse_error = |message: String| TestVectorError::Parse {
+                line: line_number,
+                message,
+            };
+            let tokens = line
+                .split('#')
+                .next()
+                .unwrap()
+                .split_whitespace()
+                .collect::<Vec<_>>();
+            match tokens.as_slice() {
+                [] => {}
+                ["pin", alias, "=", pin] | ["pin", alias, pin] => {
+                    aliases.insert(alias.to_string(), pin.to_string());
+                }
+                [tick, action, pin, value] => {
+                    let tick = tick
+                        .parse()
+                        .map_err(|_| parse_error(format!("'{tick}' is not a tick")))?;
+                    let action = match *action {
+                        "set" => Action::Set,
+                        "expect" => Action::Expect,
+                        _ => return Err(parse_error(format!("Unknown action '{action}'"))),
+                    };
+                    let value = parse_port_value(value)
+                        .ok_or_else(|| parse_error(format!("'{value}' is not a value")))?;
+                    steps.push(Step {
+                        line: line_number,
+                        tick,
+                        action,
+                        pin: pin.to_string(),
+                        value,
+                    });
+                }
+                _ => {
+                    return Err(parse_error(
+                        "Expected 'pin <alias> = <pin>' or '<tick> set|expect <pin> <value>'"
+                            .into(),
+                    ))
+                }
+            }
+        }
+        steps.sort_by_key(|step| step.tick);
+        Ok(Self { aliases, steps })
+    }
+}
+
+impl TestVectors {
+    pub fn expectation_count(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| step.action == Action::Expect)
+            .count()
+    }
+
+    /// The last tick with anything to set or check
+    pub fn last_tick(&self) -> usize {
+        self.steps.last().map_or(0, |step| step.tick)
+    }
+
+    /// Applies the stimulus to `scene`, returning every expectation that did not hold
+    pub fn run(&self, scene: &mut Scene) -> Result<Vec<Mismatch>, TestVectorError> {
+        for step in &self.steps {
+            self.resolve(scene, step)?;
+        }
+        let mut mismatches = self.apply(scene, 0)?;
+        for tick in 1..=self.last_tick() {
+            scene.update();
+            mismatches.extend(self.apply(scene, tick)?);
+        }
+        Ok(mismatches)
+    }
+
+    /// Checks the expectations for `tick` and then sets its inputs, for running the vectors one tick at a time.
+    /// `scene` should have been updated `tick` times since the vectors started
+    pub fn apply(&self, scene: &mut Scene, tick: usize) -> Result<Vec<Mismatch>, TestVectorError> {
+        let start = self.steps.partition_point(|step| step.tick < tick);
+        let end = self.steps.partition_point(|step| step.tick <= tick);
+        let mut mismatches = vec![];
+        let mut sets = vec![];
+        for step in &self.steps[start..end] {
+            let target = self.resolve(scene, step)?;
+            match step.action {
+                Action::Set => sets.push((step.line, target, step.value)),
+                Action::Expect => {
+                    let actual = match &target {
+                        Target::Pin(pin) => scene.get_pin_state(*pin) as u64,
+                        Target::Port(name) => {
+                            scene
+                                .get_port(name)
+                                .map_err(|error| TestVectorError::Port {
+                                    line: step.line,
+                                    error,
+                                })?
+                        }
+                    };
+                    if actual != step.value {
+                        mismatches.push(Mismatch {
+                            line: step.line,
+                            tick,
+                            pin: step.pin.clone(),
+                            expected: step.value,
+                            actual,
+                        });
+                    }
+                }
+            }
+        }
+        for (line, target, value) in sets {
+            match target {
+                Target::Pin(Pin::Input(id, index)) => {
+                    scene.get_component_mut(id).get_inputs_mut()[index].state = value != 0;
+                }
+                Target::Pin(_) => {}
+                Target::Port(name) => scene
+                    .set_port(&name, value)
+                    .map_err(|error| TestVectorError::Port { line, error })?,
+            }
+        }
+        Ok(mismatches)
+    }
+
+    fn resolve(&self, scene: &Scene, step: &Step) -> Result<Target, TestVectorError> {
+        let name = self.aliases.get(&step.pin).unwrap_or(&step.pin);
+        if let Ok(port) = scene.find_port(name) {
+            if step.action == Action::Set && port.direction == PortDirection::Output {
+                return Err(TestVectorError::Port {
+                    line: step.line,
+                    error: PortError::NotAnInput(port.name),
+                });
+            }
+            return Ok(Target::Port(port.name));
+        }
+        if step.value > 1 {
+            return Err(TestVectorError::NotABit {
+                line: step.line,
+                pin: step.pin.clone(),
+            });
+        }
+        let pin = scene
+            .find_pin(name)
+            .ok_or_else(|| TestVectorError::NoSuchPin {
+                line: step.line,
+                pin: step.pin.clone(),
+            })?;
+        if step.action == Action::Set {
+            match pin {
+                Pin::Input(id, index) if scene.get_driver(id, index).is_none() => {}
+                _ => {
+                    return Err(TestVectorError::NotSettable {
+                        line: step.line,
+                        pin: step.pin.clone(),
+                    })
+                }
+            }
+        }
+        Ok(Target::Pin(pin))
+    }
+
+    /// Runs the vectors and panics with every mismatch if any expectation did not hold, for use in tests
+    pub fn assert_passes(&self, scene: &mut Scene) {
+        match self.run(scene) {
+            Ok(mismatches) if mismatches.is_empty() => {}
+            Ok(mismatches) => panic!(
+                "{} of {} expectations failed:\n{}",
+                mismatches.len(),
+                self.expectation_count(),
+                mismatches
+                    .iter()
+                    .map(|mismatch| mismatch.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+            Err(error) => panic!("{error}"),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+};
+
+use crate::{Pin, Scene, Update};
+
+/// Records the state of chosen pins on every tick, and writes them out as a Value Change Dump.
+///
+/// Pins can be watched at any time, their state on ticks recorded before that is unknown.
+#[derive(Default)]
+pub struct Tracer {
+    signals: Vec<(String, Pin)>,
+    samples: VecDeque<Vec<bool>>,
+    first_tick: usize,
+    max_samples: Option<usize>,
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Only keeps the most recent `max_samples` ticks
+    pub fn with_max_samples(max_samples: usize) -> Self {
+        Self {
+            max_samples: Some(max_samples),
+            ..Default::default()
+        }
+    }
+
+    pub fn watch(&mut self, scene: &Scene, pin: Pin) {
+        self.watch_named(scene.get_pin_name(pin), pin);
+    }
+
+    pub fn watch_named(&mut self, name: impl Into<String>, pin: Pin) {
+        self.signals.push((name.into(), pin));
+    }
+
+    pub fn unwatch(&mut self, pin: Pin) {
+        while let Some(index) = self.signals.iter().position(|&(_, other)| other == pin) {
+            self.signals.remove(index);
+            for sample in &mut self.samples {
+                if index < sample.len() {
+                    sample.remove(index);
+                }
+            }
+        }
+    }
+
+    pub fn is_watched(&self, pin: Pin) -> bool {
+        self.signals.iter().any(|&(_, other)| other == pin)
+    }
+
+    pub fn get_signals(&self) -> &[(String, Pin)] {
+        &self.signals
+    }
+
+    pub fn first_tick(&self) -> usize {
+        self.first_tick
+    }
+
+    /// The tick that the next sample will be recorded as
+    pub fn end_tick(&self) -> usize {
+        self.first_tick + self.samples.len()
+    }
+
+    /// The state of the watched signal at `index` on `tick`, if it was recorded
+    pub fn get_state(&self, tick: usize, index: usize) -> Option<bool> {
+        self.samples
+            .get(tick.checked_sub(self.first_tick)?)?
+            .get(index)
+            .copied()
+    }
+
+    /// Records the current state of every watched pin as the next tick
+    pub fn sample(&mut self, scene: &Scene) {
+        self.samples.push_back(
+            self.signals
+                .iter()
+                .map(|&(_, pin)| scene.get_pin_state(pin))
+                .collect(),
+        );
+        if self.max_samples.is_some_and(|max| self.samples.len() > max) {
+            self.samples.pop_front();
+            self.first_tick += 1;
+        }
+    }
+
+    /// Forgets every tick from `tick` onwards, so that the next sample is recorded as `tick`.
+    /// Skipping ahead past the last sample forgets everything, since the ticks in between are unknown
+    pub fn truncate(&mut self, tick: usize) {
+        if tick < self.first_tick || tick > self.end_tick() {
+            self.samples.clear();
+            self.first_tick = tick;
+        } else {
+            self.samples.truncate(tick - self.first_tick);
+        }
+    }
+
+    pub fn update(&mut self, scene: &mut Scene) -> Update {
+        let update = scene.update();
+        self.sample(scene);
+        update
+    }
+
+    pub fn write_vcd(&self, w: &mut impl Write) -> io::Result<()> {
+        fn identifier(mut index: usize) -> String {
+            // Identifiers are made of the printable ascii characters from '!' to '~'
+            let mut identifier = String::new();
+            loop {
+                identifier.push((b'!' + (index % 94) as u8) as char);
+                index /= 94;
+                if index == 0 {
+                    break identifier;
+                }
+                index -= 1;
+            }
+        }
+
+        fn value(state: Option<bool>) -> char {
+            match state {
+                Some(true) => '1',
+                Some(false) => '0',
+                None => 'x',
+            }
+        }
+
+        writeln!(w, "$timescale 1ns $end")?;
+        writeln!(w, "$scope module logic_sim $end")?;
+        for (i, (name, _)) in self.signals.iter().enumerate() {
+            writeln!(w, "$var wire 1 {} {name} $end", identifier(i))?;
+        }
+        writeln!(w, "$upscope $end")?;
+        writeln!(w, "$enddefinitions $end")?;
+
+        writeln!(w, "#{}", self.first_tick)?;
+        writeln!(w, "$dumpvars")?;
+        for i in 0..self.signals.len() {
+            writeln!(
+                w,
+                "{}{}",
+                value(self.get_state(self.first_tick, i)),
+                identifier(i)
+            )?;
+        }
+        writeln!(w, "$end")?;
+        for tick in self.first_tick + 1..self.end_tick() {
+            let changes = (0..self.signals.len())
+                .filter(|&i| self.get_state(tick, i) != self.get_state(tick - 1, i))
+                .collect::<Vec<_>>();
+            if !changes.is_empty() {
+                writeln!(w, "#{tick}")?;
+                for i in changes {
+                    writeln!(w, "{}{}", value(self.get_state(tick, i)), identifier(i))?;
+                }
+            }
+        }
+        writeln!(w, "#{}", self.end_tick())?;
+        Ok(())
+    }
+}
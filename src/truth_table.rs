@@ -0,0 +1,460 @@
+use std::fmt::Display;
+
+use derive_more::Display;
+
+use crate::{ComponentType, Expression, ExpressionError, Pin, Scene, Stability};
+
+#[derive(Clone, Debug, Display)]
+pub enum TruthTableError {
+    #[display(fmt = "{_0} is not an input that nothing is connected to or a bit of an input port")]
+    NotAnInput(Pin),
+    #[display(fmt = "Too many inputs, {_0} would need over a million rows")]
+    TooManyInputs(usize),
+    #[display(fmt = "{stability} with inputs {}", "format_states(inputs)")]
+    DidNotSettle {
+        inputs: Vec<bool>,
+        stability: Stability,
+    },
+    #[display(fmt = "Line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[display(fmt = "{_0} rows is not every combination of the inputs")]
+    Incomplete(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TruthTable {
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    rows: Vec<(Vec<bool>, Vec<bool>)>,
+}
+
+impl TruthTable {
+    pub const MAX_INPUTS: usize = 20;
+
+    /// Tries every combination of `inputs`, starting from the current state of `scene` each time, and
+    /// records the state of `outputs` once the scene settles. Rows count up in binary with the first input as the highest bit.
+    /// Inputs can also be the bits of input ports
+    pub fn generate(
+        scene: &Scene,
+        inputs: &[Pin],
+        outputs: &[Pin],
+        max_ticks: usize,
+    ) -> Result<Self, TruthTableError> {
+        let drivers = scene.get_drivers();
+        let is_input = |pin: &Pin| match *pin {
+            Pin::Input(id, index) => !drivers.contains_key(&(id, index)),
+            Pin::Output(id, _) => scene.get_component(id).typ.is_input_port(),
+        };
+        if let Some(&pin) = inputs.iter().find(|pin| !is_input(pin)) {
+            return Err(TruthTableError::NotAnInput(pin));
+        }
+        if inputs.len() > Self::MAX_INPUTS {
+            return Err(TruthTableError::TooManyInputs(inputs.len()));
+        }
+
+        let mut scene = scene.clone();
+        let start = scene.snapshot();
+        let mut rows = vec![];
+        for combination in 0..1usize << inputs.len() {
+            scene.restore(&start);
+            let input_states = (0..inputs.len())
+                .map(|i| combination & (1 << (inputs.len() - 1 - i)) != 0)
+                .collect::<Vec<_>>();
+            for (&pin, &state) in inputs.iter().zip(&input_states) {
+                match pin {
+                    Pin::Input(id, index) => {
+                        scene.get_component_mut(id).get_inputs_mut()[index].state = state;
+                    }
+                    Pin::Output(id, index) => {
+                        if let ComponentType::InputPort { value, .. } =
+                            &mut scene.get_component_mut(id).typ
+                        {
+                            *value = *value & !(1 << index) | (state as u64) << index;
+                        }
+                    }
+                }
+            }
+            let stability = scene.run_until_stable(max_ticks);
+            if !matches!(stability, Stability::Stable { .. }) {
+                return Err(TruthTableError::DidNotSettle {
+                    inputs: input_states,
+                    stability,
+                });
+            }
+            let output_states = outputs
+                .iter()
+                .map(|&pin| scene.get_pin_state(pin))
+                .collect();
+            rows.push((input_states, output_states));
+        }
+
+        Ok(Self {
+            inputs: inputs.iter().map(|&pin| scene.get_pin_name(pin)).collect(),
+            outputs: outputs.iter().map(|&pin| scene.get_pin_name(pin)).collect(),
+            rows,
+        })
+    }
+
+    /// Reads a table written by [`TruthTable::to_csv`], [`TruthTable::to_markdown`] or [`Display`].
+    /// A table with `2^n` rows has `n` inputs, which are the first columns
+    pub fn parse(text: &str) -> Result<Self, TruthTableError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                (
+                    i + 1,
+                    line.split([',', '|', ' ', '\t'])
+                        .filter(|cell| !cell.is_empty())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            // Skips blank lines and the lines under the names
+            .filter(|(_, cells)| {
+                !cells
+                    .iter()
+                    .all(|cell| cell.chars().all(|c| matches!(c, '-' | ':' | '+')))
+            });
+
+        let Some((_, names)) = lines.next() else {
+            return Err(TruthTableError::Parse {
+                line: 1,
+                message: "There are no column names".into(),
+            });
+        };
+        let rows = lines
+            .map(|(line, cells)| {
+                if cells.len() != names.len() {
+                    return Err(TruthTableError::Parse {
+                        line,
+                        message: format!("Expected {} columns, found {}", names.len(), cells.len()),
+                    });
+                }
+                cells
+                    .iter()
+                    .map(|&cell| match cell {
+                        "0" => Ok(false),
+                        "1" => Ok(true),
+                        _ => Err(TruthTableError::Parse {
+                            line,
+                            message: format!("'{cell}' is not 0 or 1"),
+                        }),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !rows.len().is_power_of_two() {
+            return Err(TruthTableError::Incomplete(rows.len()));
+        }
+        let input_count = rows.len().trailing_zeros() as usize;
+        if input_count > Self::MAX_INPUTS {
+            return Err(TruthTableError::TooManyInputs(input_count));
+        }
+        if input_count > names.len() {
+            return Err(TruthTableError::Incomplete(rows.len()));
+        }
+        let mut seen = vec![false; rows.len()];
+        for row in &rows {
+            let combination = combination(&row[..input_count]);
+            if seen[combination] {
+                return Err(TruthTableError::Incomplete(rows.len()));
+            }
+            seen[combination] = true;
+        }
+
+        Ok(Self {
+            inputs: names[..input_count]
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            outputs: names[input_count..]
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            rows: rows
+                .into_iter()
+                .map(|mut row| {
+                    let outputs = row.split_off(input_count);
+                    (row, outputs)
+                })
+                .collect(),
+        })
+    }
+
+    /// A minimised sum of products for each output, see [`Expression::from_minterms`]
+    pub fn to_expressions(&self) -> Result<Vec<(String, Expression)>, ExpressionError> {
+        self.outputs
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let minterms = self
+                    .rows
+                    .iter()
+                    .filter(|(_, outputs)| outputs[i])
+                    .map(|(inputs, _)| combination(inputs))
+                    .collect::<Vec<_>>();
+                Ok((
+                    name.clone(),
+                    Expression::from_minterms(self.inputs.clone(), &minterms)?,
+                ))
+            })
+            .collect()
+    }
+
+    pub fn get_inputs(&self) -> &[String] {
+        &self.inputs
+    }
+
+    pub fn get_outputs(&self) -> &[String] {
+        &self.outputs
+    }
+
+    pub fn get_rows(&self) -> &[(Vec<bool>, Vec<bool>)] {
+        &self.rows
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = self
+            .inputs
+            .iter()
+            .chain(&self.outputs)
+            .map(|name| name.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        csv.push('\n');
+        for (inputs, outputs) in &self.rows {
+            csv += &inputs
+                .iter()
+                .chain(outputs)
+                .map(|&state| if state { "1" } else { "0" })
+                .collect::<Vec<_>>()
+                .join(",");
+            csv.push('\n');
+        }
+        csv
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+        let columns = self.inputs.iter().chain(&self.outputs).collect::<Vec<_>>();
+        markdown += &format!(
+            "| {} |\n",
+            columns
+                .iter()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join(" | ")
+        );
+        markdown += &format!(
+            "|{}|\n",
+            columns
+                .iter()
+                .map(|name| format!(":{}:", "-".repeat(name.len().max(1))))
+                .collect::<Vec<_>>()
+                .join("|")
+        );
+        for (inputs, outputs) in &self.rows {
+            markdown += &format!(
+                "| {} |\n",
+                inputs
+                    .iter()
+                    .chain(outputs)
+                    .zip(&columns)
+                    .map(|(&state, name)| format!("{:^width$}", state as u8, width = name.len()))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            );
+        }
+        markdown
+    }
+}
+
+impl Display for TruthTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let inputs = self.inputs.join(" ");
+        let outputs = self.outputs.join(" ");
+        writeln!(f, "{inputs} | {outputs}")?;
+        writeln!(
+            f,
+            "{}-+-{}",
+            "-".repeat(inputs.len()),
+            "-".repeat(outputs.len())
+        )?;
+        for (input_states, output_states) in &self.rows {
+            let row = |names: &[String], states: &[bool]| {
+                names
+                    .iter()
+                    .zip(states)
+                    .map(|(name, &state)| format!("{:^width$}", state as u8, width = name.len()))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            writeln!(
+                f,
+                "{:width$} | {}",
+                row(&self.inputs, input_states),
+                row(&self.outputs, output_states),
+                width = inputs.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// The first state is the highest bit
+fn combination(states: &[bool]) -> usize {
+    states
+        .iter()
+        .fold(0, |combination, &state| combination << 1 | state as usize)
+}
+
+fn format_states(states: &[bool]) -> String {
+    states
+        .iter()
+        .map(|&state| if state { '1' } else { '0' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CircuitBuilder;
+
+    // `x or y` and `not z`, with each input read once
+    fn table() -> TruthTable {
+        let mut b = CircuitBuilder::new();
+        let x = b.input("x");
+        let y = b.input("y");
+        let z = b.input("z");
+        let or = b.or(x, y);
+        let not = b.not(z);
+        b.output("or", or);
+        b.output("not", not);
+        let circuit = b.build();
+        let inputs = circuit
+            .inputs
+            .iter()
+            .map(|(_, pins)| pins[0])
+            .collect::<Vec<_>>();
+        let outputs = circuit
+            .outputs
+            .iter()
+            .map(|&(_, pin)| pin)
+            .collect::<Vec<_>>();
+        TruthTable::generate(&circuit.scene, &inputs, &outputs, 10).unwrap()
+    }
+
+    #[test]
+    fn generates_tables() {
+        let table = table();
+        assert_eq!(table.get_inputs().len(), 3);
+        assert_eq!(table.get_outputs().len(), 2);
+        for (combination, (inputs, outputs)) in table.get_rows().iter().enumerate() {
+            let [x, y, z] = [4, 2, 1].map(|bit| combination & bit != 0);
+            assert_eq!(inputs, &[x, y, z]);
+            assert_eq!(outputs, &[x || y, !z]);
+        }
+    }
+
+    #[test]
+    fn generates_tables_from_ports() {
+        let mut b = CircuitBuilder::new();
+        let [a0, a1] = b.input_port("a", 2).try_into().ok().unwrap();
+        let or = b.or(a0, a1);
+        let y = b.output_port("y", [or]);
+        let mut circuit = b.build();
+        circuit.scene.set_port("a", 3).unwrap();
+        let port = circuit.scene.ports()[0].component;
+        let table = TruthTable::generate(
+            &circuit.scene,
+            &[Pin::Output(port, 1), Pin::Output(port, 0)],
+            &[Pin::Input(y, 0)],
+            10,
+        )
+        .unwrap();
+        let outputs = table
+            .get_rows()
+            .iter()
+            .map(|(_, outputs)| outputs[0])
+            .collect::<Vec<_>>();
+        assert_eq!(outputs, [false, true, true, true]);
+    }
+
+    #[test]
+    fn rejects_bad_inputs() {
+        let mut b = CircuitBuilder::new();
+        let x = b.input("x");
+        let not = b.not(x);
+        let again = b.not(not);
+        b.output("again", again);
+        let circuit = b.build();
+        let driven = circuit.outputs[0].1;
+        let Pin::Output(id, _) = driven else {
+            unreachable!()
+        };
+        let driven_input = Pin::Input(id, 0);
+        assert!(matches!(
+            TruthTable::generate(&circuit.scene, &[driven_input], &[driven], 10),
+            Err(TruthTableError::NotAnInput(pin)) if pin == driven_input
+        ));
+        assert!(matches!(
+            TruthTable::generate(&circuit.scene, &[driven], &[driven], 10),
+            Err(TruthTableError::NotAnInput(pin)) if pin == driven
+        ));
+        let inputs = vec![circuit.inputs[0].1[0]; TruthTable::MAX_INPUTS + 1];
+        assert!(matches!(
+            TruthTable::generate(&circuit.scene, &inputs, &[driven], 10),
+            Err(TruthTableError::TooManyInputs(21))
+        ));
+    }
+
+    #[test]
+    fn reports_oscillation() {
+        let mut b = CircuitBuilder::new();
+        let x = b.input("x");
+        let (q, feedback) = b.feedback(false);
+        let or = b.or(x, q);
+        let not = b.not(or);
+        b.close(feedback, not);
+        let circuit = b.build();
+        let input = circuit.inputs[0].1[0];
+        let error = TruthTable::generate(&circuit.scene, &[input], &[], 10).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Oscillating every 2 ticks from tick 1 with inputs 0"
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let table = table();
+        assert_eq!(TruthTable::parse(&table.to_csv()).unwrap(), table);
+        assert_eq!(TruthTable::parse(&table.to_markdown()).unwrap(), table);
+        assert_eq!(TruthTable::parse(&table.to_string()).unwrap(), table);
+    }
+
+    #[test]
+    fn parses_tables() {
+        let table = TruthTable::parse("a,b,y\n0,0,0\n0,1,1\n1,0,1\n1,1,0\n").unwrap();
+        assert_eq!(table.get_inputs(), ["a", "b"]);
+        assert_eq!(table.get_outputs(), ["y"]);
+        assert_eq!(table.to_expressions().unwrap()[0].0, "y");
+
+        let error = |text: &str| TruthTable::parse(text).unwrap_err().to_string();
+        assert_eq!(error(""), "Line 1: There are no column names");
+        assert_eq!(
+            error("a,y\n0,1\n1\n"),
+            "Line 3: Expected 2 columns, found 1"
+        );
+        assert_eq!(error("a,y\n0,1\n1,x\n"), "Line 3: 'x' is not 0 or 1");
+        assert_eq!(
+            error("a,b,y\n0,0,0\n0,1,1\n1,0,1\n"),
+            "3 rows is not every combination of the inputs"
+        );
+        assert_eq!(
+            error("a,y\n0,1\n0,0\n"),
+            "2 rows is not every combination of the inputs"
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+};
+
+use crate::{identifier, AssertCondition, ComponentType, Pin, Scene};
+
+impl Scene {
+    /// Writes the scene as a structural Verilog module. Inputs with nothing connected become input ports and
+    /// outputs that are not connected become output ports, both named after their pins.
+    /// Every connection is a wire named after the output driving it, and each delay is a register clocked by `clk`.
+    /// Tunnels are an `assign` of the inputs with their name that something drives, so the tunnels that only
+    /// receive a signal do not add ports.
+    /// Asserts become `$error` checks, which only simulators act on.
+    ///
+    /// When the scene has input or output ports, they are the module's only ports besides `clk`. Other inputs with
+    /// nothing connected are then tied to their current state, and outputs that are not connected are left as wires.
+    /// Port names are turned into identifiers, which fails with [`io::ErrorKind::InvalidInput`] when a port has no
+    /// name or two ports end up with the same one. Names that are Verilog keywords get an underscore on the end,
+    /// and pins whose names are already taken get a number on the end
+    pub fn write_verilog(&self, module_name: &str, w: &mut impl Write) -> io::Result<()> {
+        let has_delays = self
+            .into_iter()
+            .any(|id| self.get_component(id).typ.is_delay());
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+        let mut taken = HashSet::new();
+        if has_delays {
+            taken.insert("clk".to_string());
+        }
+        let mut port_names = HashMap::new();
+        for port in self.ports() {
+            if port.name.trim().is_empty() {
+                return Err(invalid(format!("Port {} has no name", port.component)));
+            }
+            let name = escape(identifier(&port.name));
+            if has_delays && name == "clk" {
+                return Err(invalid(
+                    "A port cannot be called clk, which is the clock".to_string(),
+                ));
+            }
+            if !taken.insert(name.clone()) {
+                return Err(invalid(format!(
+                    "More than one port would be called {name}"
+                )));
+            }
+            port_names.insert(port.component, name);
+        }
+        // Instances share the module's namespace with the wires
+        for id in self {
+            let instance = format!("{}_{id}", self.get_component(id).get_name());
+            taken.insert(format!("{instance}_state"));
+            taken.insert(instance);
+        }
+        let mut pin_names = HashMap::new();
+        for id in self {
+            let component = self.get_component(id);
+            let pins = (0..component.get_inputs().len())
+                .map(|index| Pin::Input(id, index))
+                .chain((0..component.get_outputs().len()).map(|index| Pin::Output(id, index)));
+            for pin in pins {
+                let base = escape(self.get_pin_name(pin));
+                let mut name = base.clone();
+                for suffix in 2.. {
+                    if taken.insert(name.clone()) {
+                        break;
+                    }
+                    name = format!("{base}_{suffix}");
+                }
+                pin_names.insert(pin, name);
+            }
+        }
+        let pin_name = |pin: Pin| pin_names[&pin].clone();
+
+        let drivers = self.get_drivers();
+        let input_net = |pin: Pin| {
+            let Pin::Input(id, index) = pin else {
+                unreachable!()
+            };
+            pin_name(drivers.get(&(id, index)).copied().unwrap_or(pin))
+        };
+        // Tunnels read every input that is driven in their group, or every input when none of them are
+        let tunnel_inputs = |name: &str| {
+            let inputs = self.get_tunnels(name).collect::<Vec<_>>();
+            let driven = inputs
+                .iter()
+                .copied()
+                .filter(|&id| drivers.contains_key(&(id, 0)))
+                .collect::<Vec<_>>();
+            if driven.is_empty() {
+                inputs
+            } else {
+                driven
+            }
+        };
+
+        // Bits of ports that are wider than one are indexed
+        let bit = |name: &str, width: usize, index: usize| {
+            if width > 1 {
+                format!("{name}[{index}]")
+            } else {
+                name.to_string()
+            }
+        };
+        let range = |width: usize| {
+            if width > 1 {
+                format!("[{}:0] ", width - 1)
+            } else {
+                String::new()
+            }
+        };
+
+        let has_ports = !port_names.is_empty();
+        let mut ports = vec![];
+        let mut wires = vec![];
+        for id in self {
+            let component = self.get_component(id);
+            match &component.typ {
+                ComponentType::InputPort { outputs, .. } => {
+                    ports.push(format!("input {}{}", range(outputs.len()), port_names[&id]));
+                }
+                ComponentType::OutputPort { inputs, .. } => {
+                    ports.push(format!("output {}{}", range(inputs.len()), port_names[&id]));
+                }
+                _ => {}
+            }
+            let receives_only = match &component.typ {
+                ComponentType::Tunnel { name, .. } => !tunnel_inputs(name).contains(&id),
+                _ => false,
+            };
+            for (index, input) in component.get_inputs().iter().enumerate() {
+                if drivers.contains_key(&(id, index)) || receives_only {
+                    continue;
+                }
+                let name = pin_name(Pin::Input(id, index));
+                if has_ports {
+                    wires.push(format!("{name} = 1'b{}", input.state as u8));
+                } else {
+                    ports.push(format!("input {name}"));
+                }
+            }
+            for (index, output) in component.get_outputs().iter().enumerate() {
+                let name = pin_name(Pin::Output(id, index));
+                match output {
+                    Some(_) => wires.push(name),
+                    None if component.typ.is_tunnel() || component.typ.is_input_port() => {}
+                    None if has_ports => wires.push(name),
+                    None => ports.push(format!("output {name}")),
+                }
+            }
+        }
+        if has_delays {
+            ports.insert(0, "input clk".to_string());
+        }
+
+        writeln!(w, "module {module_name} (")?;
+        for (i, port) in ports.iter().enumerate() {
+            let separator = if i + 1 < ports.len() { "," } else { "" };
+            writeln!(w, "    {port}{separator}")?;
+        }
+        writeln!(w, ");")?;
+        for wire in &wires {
+            writeln!(w, "    wire {wire};")?;
+        }
+        if !wires.is_empty() {
+            writeln!(w)?;
+        }
+
+        for id in self {
+            let component = self.get_component(id);
+            let instance = format!("{}_{id}", component.get_name());
+            // Asserts and output ports have no output
+            let output = pin_names
+                .get(&Pin::Output(id, 0))
+                .cloned()
+                .unwrap_or_default();
+            match &component.typ {
+                ComponentType::Not { .. } => {
+                    writeln!(
+                        w,
+                        "    not {instance} ({output}, {});",
+                        input_net(Pin::Input(id, 0))
+                    )?;
+                }
+                ComponentType::Or { inputs, .. } => {
+                    let inputs = (0..inputs.len())
+                        .map(|index| input_net(Pin::Input(id, index)))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    writeln!(w, "    or {instance} ({output}, {inputs});")?;
+                }
+                ComponentType::Delay {
+                    state_last_frame, ..
+                } => {
+                    let state = format!("{instance}_state");
+                    writeln!(w, "    reg {state} = 1'b{};", *state_last_frame as u8)?;
+                    writeln!(w, "    always @(posedge clk)")?;
+                    writeln!(w, "        {state} <= {};", input_net(Pin::Input(id, 0)))?;
+                    writeln!(w, "    assign {output} = {state};")?;
+                }
+                ComponentType::Assert { inputs, condition } => {
+                    let inputs = (0..inputs.len())
+                        .map(|index| input_net(Pin::Input(id, index)))
+                        .collect::<Vec<_>>();
+                    let holds = match condition {
+                        AssertCondition::AlwaysHigh => inputs.join(" & "),
+                        AssertCondition::NeverHigh => format!("!({})", inputs.join(" | ")),
+                        AssertCondition::Equal if inputs.len() < 2 => "1'b1".to_string(),
+                        AssertCondition::Equal => inputs[1..]
+                            .iter()
+                            .map(|input| format!("{input} == {}", inputs[0]))
+                            .collect::<Vec<_>>()
+                            .join(" && "),
+                    };
+                    writeln!(
+                        w,
+                        "    always @* if (!({holds})) $error(\"{instance} failed\");"
+                    )?;
+                }
+                ComponentType::Tunnel { .. } if component.get_outputs()[0].is_none() => {}
+                ComponentType::Tunnel { name, .. } => {
+                    let inputs = tunnel_inputs(name)
+                        .into_iter()
+                        .map(|id| input_net(Pin::Input(id, 0)))
+                        .collect::<Vec<_>>()
+                        .join(" | ");
+                    writeln!(w, "    assign {output} = {inputs};")?;
+                }
+                ComponentType::InputPort { outputs, .. } => {
+                    let name = &port_names[&id];
+                    for (index, output) in outputs.iter().enumerate() {
+                        if output.is_some() {
+                            let pin = pin_name(Pin::Output(id, index));
+                            writeln!(w, "    assign {pin} = {};", bit(name, outputs.len(), index))?;
+                        }
+                    }
+                }
+                ComponentType::OutputPort { inputs, .. } => {
+                    for index in 0..inputs.len() {
+                        writeln!(
+                            w,
+                            "    assign {} = {};",
+                            bit(&port_names[&id], inputs.len(), index),
+                            input_net(Pin::Input(id, index))
+                        )?;
+                    }
+                }
+            }
+        }
+        writeln!(w, "endmodule")?;
+        Ok(())
+    }
+}
+
+// The reserved words of IEEE 1364-2005
+const KEYWORDS: &str = "\
+    always and assign automatic begin buf bufif0 bufif1 case casex casez cell cmos config deassign default defparam \
+    design disable edge else end endcase endconfig endfunction endgenerate endmodule endprimitive endspecify \
+    endtable endtask event for force forever fork function generate genvar highz0 highz1 if ifnone incdir include \
+    initial inout input instance integer join large liblist library localparam macromodule medium module nand \
+    negedge nmos nor noshowcancelled not notif0 notif1 or output parameter pmos posedge primitive pull0 pull1 \
+    pulldown pullup pulsestyle_ondetect pulsestyle_onevent rcmos real realtime reg release repeat rnmos rpmos rtran \
+    rtranif0 rtranif1 scalared showcancelled signed small specify specparam strong0 strong1 supply0 supply1 table \
+    task time tran tranif0 tranif1 tri tri0 tri1 triand trior trireg unsigned use uwire vectored wait wand weak0 \
+    weak1 while wire wor xnor xor";
+
+// Identifiers are case sensitive, so only exact matches clash with a keyword
+fn escape(mut identifier: String) -> String {
+    if KEYWORDS
+        .split_whitespace()
+        .any(|keyword| keyword == identifier)
+    {
+        identifier.push('_');
+    }
+    identifier
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CircuitBuilder, ComponentID};
+
+    #[test]
+    fn writes_modules() {
+        let mut b = CircuitBuilder::new();
+        let [a0, a1] = b.input_port("input", 2).try_into().ok().unwrap();
+        let or = b.or(a0, a1);
+        let not = b.not(or);
+        b.output_port("out", [not]);
+        let x = b.input("x");
+        let n = b.not(x);
+        b.assert(AssertCondition::NeverHigh, [n]);
+        let mut scene = b.build().scene;
+        // Names that are keywords, whether they come from ports or labels, are escaped
+        scene.set_pin_label(Pin::Output(ComponentID(1), 0), Some("wire".to_string()));
+        scene.set_pin_label(Pin::Input(ComponentID(4), 0), Some("reg".to_string()));
+
+        let mut verilog = vec![];
+        scene.write_verilog("top", &mut verilog).unwrap();
+        let verilog = String::from_utf8(verilog).unwrap();
+        assert_eq!(
+            verilog,
+            r#"module top (
+    input [1:0] input_,
+    output out
+);
+    wire input_out0;
+    wire input_out1;
+    wire wire_;
+    wire Not_2_out0;
+    wire reg_ = 1'b0;
+    wire Not_4_out0;
+
+    assign input_out0 = input_[0];
+    assign input_out1 = input_[1];
+    or Or_1 (wire_, input_out0, input_out1);
+    not Not_2 (Not_2_out0, wire_);
+    assign out = Not_2_out0;
+    not Not_4 (Not_4_out0, reg_);
+    always @* if (!(!(Not_4_out0))) $error("Assert_5 failed");
+endmodule
+"#
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use std::process::{Command, Output};
+
+use logic_sim::*;
+
+// Saves `scene` where the command line can load it and runs it with `args`
+fn run(name: &str, scene: &Scene, args: &[&str]) -> Output {
+    let path = std::env::temp_dir().join(format!("logic_sim_cli_{name}.json"));
+    std::fs::write(&path, serde_json::to_string(scene).unwrap()).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_logic_sim"))
+        .arg(&path)
+        .args(args)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    output
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn stable_scenes_pass() {
+    let mut b = CircuitBuilder::new();
+    let [a0, a1] = b.input_port("a", 2).try_into().ok().unwrap();
+    let or = b.or(a0, a1);
+    b.output_port("y", [or]);
+    let scene = b.build().scene;
+
+    let output = run("stable", &scene, &["--set", "a=2", "--format", "ports"]);
+    assert_eq!(stderr(&output), "Stable after 1 ticks\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a = 2\ny = 1\n");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn unsettled_scenes_fail() {
+    let mut b = CircuitBuilder::new();
+    let (q, feedback) = b.feedback(false);
+    let not = b.not(q);
+    b.close(feedback, not);
+    let scene = b.build().scene;
+
+    let output = run("oscillating", &scene, &[]);
+    assert_eq!(stderr(&output), "Oscillating every 2 ticks from tick 1\n");
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = run("timed_out", &scene, &["--until-stable", "2"]);
+    assert_eq!(stderr(&output), "Did not settle\n");
+    assert_eq!(output.status.code(), Some(1));
+
+    // Running for a set number of ticks does not expect the scene to settle
+    let output = run("ticks", &scene, &["--ticks", "5"]);
+    assert_eq!(output.status.code(), Some(0));
+}
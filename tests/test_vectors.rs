@@ -0,0 +1,34 @@
+use logic_sim::*;
+
+// Two bit ports `a` and `b` with `y = a | b`, and `d`, which is `c` one tick later
+fn scene() -> Scene {
+    let mut b = CircuitBuilder::new();
+    let [a0, a1] = b.input_port("a", 2).try_into().ok().unwrap();
+    let [b0, b1] = b.input_port("b", 2).try_into().ok().unwrap();
+    let (y0, y1) = (b.or(a0, b0), b.or(a1, b1));
+    b.output_port("y", [y0, y1]);
+    let c = b.input_port("c", 1).pop().unwrap();
+    let d = b.delay(c, false);
+    b.output_port("d", [d]);
+    b.build().scene
+}
+
+fn load(path: &str) -> TestVectors {
+    let path = format!("{}/tests/vectors/{path}", env!("CARGO_MANIFEST_DIR"));
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|error| panic!("Could not read {path}: {error}"))
+        .parse()
+        .unwrap_or_else(|error| panic!("{path}: {error}"))
+}
+
+#[test]
+fn ports_pass() {
+    load("ports.txt").assert_passes(&mut scene());
+}
+
+#[test]
+#[should_panic(expected = "1 of 2 expectations failed")]
+fn mismatches_fail() {
+    let vectors = "0 set a 1\n1 expect y 1\n1 expect d 1".parse::<TestVectors>();
+    vectors.unwrap().assert_passes(&mut scene());
+}